@@ -0,0 +1,352 @@
+//! Archive of completed tasks, kept in `archive.txt` next to the save file.
+use crate::todo::{Snapshot, ToDo, DATE_FORMAT, DAY_FORMAT};
+use crate::{scroll_offset, tab_num, View, READ_ONLY_MESSAGE};
+use chrono::Local;
+use log::{info, warn};
+use std::cell::RefCell;
+use std::fs::{read_to_string, write, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::rc::{Rc, Weak};
+
+/// Start of the line that begins each entry in the archive file, followed
+/// by the date it was archived and the tab-separated path of the task.
+const ENTRY_PREFIX: &str = "# ";
+
+/// A task archived along with its sub-tasks.
+struct Entry {
+    /// When the task was archived.
+    archived: String,
+    /// Tasks above the archived task, starting from the top level.
+    path: Vec<String>,
+    todo: Rc<RefCell<ToDo>>,
+}
+
+impl Entry {
+    /// Convert to the archive file format.
+    fn to_text(&self) -> String {
+        let mut header = format!("{}{}", ENTRY_PREFIX, self.archived);
+        for task in self.path.iter() {
+            header.push('\t');
+            header.push_str(task);
+        }
+        format!("{}\n{}", header, self.todo.borrow().to_save_string())
+    }
+}
+
+/// Parse a task and its sub-tasks from save file format.
+fn parse_subtree(lines: &[&str]) -> Option<Rc<RefCell<ToDo>>> {
+    let (first, rest) = lines.split_first()?;
+    let top = Rc::new(RefCell::new(ToDo::from_string(first, Weak::new())));
+    // Last task seen at each level of indentation
+    let mut stack = vec![Rc::clone(&top)];
+    for line in rest {
+        let tabs = tab_num(line);
+        if tabs == 0 || tabs > stack.len() {
+            return None;
+        }
+        stack.truncate(tabs);
+        let parent = stack.last()?;
+        let todo = ToDo::from_string(line.trim_start(), Rc::downgrade(parent));
+        let todo = Rc::new(RefCell::new(todo));
+        parent.borrow_mut().sub_tasks.push(Rc::clone(&todo));
+        stack.push(todo);
+    }
+    Some(top)
+}
+
+/// Read all entries from the archive file.
+fn read_archive(filename: &Path) -> Vec<Entry> {
+    let text = match read_to_string(filename) {
+        Ok(text) => text,
+        Err(_) => return Vec::new(),
+    };
+    let mut entries = Vec::new();
+    let mut lines = text.lines().peekable();
+    while let Some(header) = lines.next() {
+        let header = match header.strip_prefix(ENTRY_PREFIX) {
+            Some(header) => header,
+            None => {
+                warn!("Unexpected line in archive: {}", header);
+                continue;
+            }
+        };
+        let mut fields = header.split('\t');
+        let archived = fields.next().unwrap_or("").to_string();
+        let path = fields.map(String::from).collect();
+
+        let mut body = Vec::new();
+        while let Some(line) = lines.next_if(|line| !line.starts_with(ENTRY_PREFIX)) {
+            body.push(line);
+        }
+        match parse_subtree(&body) {
+            Some(todo) => entries.push(Entry {
+                archived,
+                path,
+                todo,
+            }),
+            None => warn!("Unable to parse archived task from {}.", archived),
+        }
+    }
+    entries
+}
+
+/// Add entries to the end of the archive file.
+fn append_archive(filename: &Path, entries: &[Entry]) -> Result<(), ()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(filename)
+        .map_err(|err| warn!("Unable to open archive: {}", err))?;
+    for entry in entries {
+        file.write_all(entry.to_text().as_bytes())
+            .map_err(|err| warn!("Unable to write to archive: {}", err))?;
+    }
+    Ok(())
+}
+
+/// Replace the contents of the archive file.
+fn write_archive(filename: &Path, entries: &[Entry]) -> Result<(), ()> {
+    let text: String = entries.iter().map(Entry::to_text).collect();
+    write(filename, text).map_err(|err| warn!("Unable to write to archive: {}", err))
+}
+
+/// Collect the completed tasks below a task, without looking inside them.
+fn collect_complete(todo: &Rc<RefCell<ToDo>>, found: &mut Vec<Rc<RefCell<ToDo>>>) {
+    for sub_task in todo.borrow().sub_tasks.iter() {
+        if sub_task.borrow().complete {
+            found.push(Rc::clone(sub_task));
+        } else {
+            collect_complete(sub_task, found);
+        }
+    }
+}
+
+impl<'a> View<'a> {
+    /// Find the archive file, which sits next to the save file.
+    fn archive_path(&self) -> Option<PathBuf> {
+        let save_path = self.save_path()?;
+        Some(save_path.with_file_name("archive.txt"))
+    }
+
+    /// Move the completed tasks below the current task, and their
+    /// sub-tasks, into the archive.
+    pub(crate) fn archive_complete(&mut self) {
+        let mut complete = Vec::new();
+        collect_complete(&self.current_task, &mut complete);
+        let filename = match (complete.is_empty(), self.archive_path()) {
+            (false, Some(filename)) => filename,
+            _ => return,
+        };
+        let prompt = format!("Archive {} completed tasks? y/n", complete.len());
+        if !self.popup(&prompt) {
+            return;
+        }
+
+        let archived = Local::now().format(DATE_FORMAT).to_string();
+        let entries: Vec<Entry> = complete
+            .iter()
+            .map(|todo| {
+                let mut path = Vec::new();
+                let mut parent = todo.borrow().parent.upgrade();
+                while let Some(task) = parent {
+                    parent = task.borrow().parent.upgrade();
+                    if parent.is_some() {
+                        path.insert(0, task.borrow().task.clone());
+                    }
+                }
+                Entry {
+                    archived: archived.clone(),
+                    path,
+                    todo: Rc::clone(todo),
+                }
+            })
+            .collect();
+        // The archive as it was, to put back if the list can't be saved
+        let previous = read_to_string(&filename).unwrap_or_default();
+        if append_archive(&filename, &entries).is_err() {
+            return;
+        }
+
+        let snapshot = Snapshot::new(&self.current_task);
+        for todo in complete {
+            if let (Some(parent), Some(index)) =
+                (todo.borrow().parent.upgrade(), ToDo::index(&todo))
+            {
+                parent.borrow_mut().sub_tasks.remove(index);
+            }
+        }
+        // Save straight away, so that the tasks are never in both files
+        if self.save().is_err() {
+            snapshot.restore();
+            if let Err(err) = write(&filename, previous) {
+                warn!("Unable to write to archive: {}", err);
+            }
+            self.not_done("Not archived");
+            return;
+        }
+        // Undoing would bring back tasks that are now in the archive
+        self.undo.clear();
+        self.marked.clear();
+        info!("Archived {} tasks.", entries.len());
+    }
+
+    /// Browse the archive, restoring tasks to where they were archived from
+    /// or deleting them for good.
+    pub(crate) fn browse_archive(&mut self) {
+        let filename = match self.archive_path() {
+            Some(filename) => filename,
+            None => return,
+        };
+        let mut entries = read_archive(&filename);
+        let mut selection = entries.len().saturating_sub(1);
+        let mut offset = 0;
+
+        loop {
+            offset = self.draw_archive(&entries, selection, offset);
+            let config = &self.window.config;
+            let (up, down, restore, delete) = (config.up, config.down, config.focus, config.delete);
+            let exit = [config.back, config.quit, config.browse_archive];
+            let key = self.window.getch();
+            self.status = None;
+            match key {
+                Some(key) if exit.contains(&key) => return,
+                Some(key) if key == up => selection = selection.saturating_sub(1),
+                Some(key) if key == down && selection + 1 < entries.len() => selection += 1,
+                // Looking through the archive is fine while read-only
+                Some(key) if (key == restore || key == delete) && self.readonly => {
+                    self.status = Some(String::from(READ_ONLY_MESSAGE));
+                }
+                Some(key) if key == restore && selection < entries.len() => {
+                    let entry = entries.remove(selection);
+                    self.restore_entry(&entry);
+                    // Save straight away, so that the task is never in neither file
+                    if self.save().is_err() {
+                        let todo = &entry.todo;
+                        if let (Some(parent), Some(index)) =
+                            (todo.borrow().parent.upgrade(), ToDo::index(todo))
+                        {
+                            parent.borrow_mut().sub_tasks.remove(index);
+                        }
+                        entries.insert(selection, entry);
+                        self.not_done("Not restored");
+                        continue;
+                    }
+                    if write_archive(&filename, &entries).is_err() {
+                        self.status = Some(String::from(
+                            "Restored, but unable to remove it from the archive.",
+                        ));
+                    }
+                    selection = selection.min(entries.len().saturating_sub(1));
+                }
+                Some(key)
+                    if key == delete
+                        && selection < entries.len()
+                        && self.popup("Delete this task from the archive for good? y/n") =>
+                {
+                    let entry = entries.remove(selection);
+                    if write_archive(&filename, &entries).is_err() {
+                        entries.insert(selection, entry);
+                    }
+                    selection = selection.min(entries.len().saturating_sub(1));
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Put an archived task back in the tree, under the deepest task of its
+    /// path that still exists.
+    fn restore_entry(&mut self, entry: &Entry) {
+        let mut parent = self.root_task();
+        for task in entry.path.iter() {
+            let next = parent
+                .borrow()
+                .sub_tasks
+                .iter()
+                .find(|todo| todo.borrow().task == *task)
+                .cloned();
+            match next {
+                Some(next) => parent = next,
+                None => break,
+            }
+        }
+        entry.todo.borrow_mut().parent = Rc::downgrade(&parent);
+        parent.borrow_mut().sub_tasks.push(Rc::clone(&entry.todo));
+        ToDo::renew_duplicate_ids(&entry.todo, &self.root_task());
+        // Undoing could remove the task, which is no longer in the archive
+        self.undo.clear();
+    }
+
+    /// Report that a change to the archive was given up on, as the list
+    /// wasn't saved, along with the reason saving gave if any.
+    fn not_done(&mut self, what: &str) {
+        self.status = Some(match self.status.take() {
+            Some(reason) => format!("{}: {}", what, reason),
+            None => format!("{}, as the list wasn't saved.", what),
+        });
+    }
+
+    /// Display the archive, returning the new scroll offset.
+    fn draw_archive(&mut self, entries: &[Entry], selection: usize, offset: usize) -> usize {
+        if self.too_small() {
+            return offset;
+        }
+        self.window.clear();
+        self.window.hide_cursor();
+        let panels = self.panels();
+        let selected = entries.get(selection);
+
+        // Parent panel shows where the selected task was archived from
+        if let Some(entry) = selected {
+            let inner = panels.parent.inner();
+            let path = entry.path.join(": ");
+            self.window.wrap_print(inner.y, inner.x, inner.width, &path);
+        }
+        self.window.panel(&panels.parent, "Archived from");
+
+        // Selection panel, or a status message
+        self.window.panel(&panels.selection, "Selection");
+        if let Some(status) = self.status.clone() {
+            self.draw_status(&panels.selection, &status);
+        } else if let Some(entry) = selected {
+            let inner = panels.selection.inner();
+            self.window.colour_on(6, 8);
+            self.window.wrap_print(
+                inner.y,
+                inner.x + 1,
+                inner.width.saturating_sub(1),
+                &entry.todo.borrow().task,
+            );
+            self.window.colour_off();
+        }
+
+        // Archive panel, with the completion date of each task
+        let rect = panels.main;
+        let inner = rect.inner();
+        self.window.panel(&rect, "Archive");
+        let index = selected.map(|_| selection);
+        let offset = scroll_offset(offset, index, inner.height, entries.len());
+        let end = entries.len().min(offset + inner.height);
+        for (y, entry) in (inner.y..).zip(entries[offset..end].iter()) {
+            let todo = entry.todo.borrow();
+            let date = match todo.completed {
+                Some(completed) => completed.format(DAY_FORMAT).to_string(),
+                None => String::from("----------"),
+            };
+            self.window.colour_on(0, 8);
+            self.window.mvprintw(y, inner.x + 2, &date);
+            self.window.colour_off();
+            let width = inner.width.saturating_sub(date.len() + 4);
+            self.print_task(y, inner.x + date.len() + 3, width, &todo);
+        }
+        self.more_indicators(&rect, offset, entries.len() - end);
+        if let Some(i) = index {
+            self.window.colour_on(6, 8);
+            self.window.mvprintw(inner.y + i - offset, inner.x, ">");
+            self.window.colour_off();
+        }
+        self.window.refresh();
+        offset
+    }
+}
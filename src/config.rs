@@ -12,8 +12,16 @@ struct TomlConfig {
     borders: Option<Borders>,
     colours: Option<Colours>,
     keys: Option<Keys>,
+    layout: Option<TomlLayout>,
+    priorities: Option<TomlPriorities>,
     save_on_exit: Option<bool>,
     print_priority: Option<bool>,
+    mouse: Option<bool>,
+    clipboard: Option<String>,
+    auto_complete: Option<bool>,
+    auto_reopen: Option<bool>,
+    hide_complete: Option<bool>,
+    reset_recurring: Option<bool>,
 }
 
 /// Layout of [border] section of config.toml file.
@@ -43,7 +51,7 @@ struct Colours {
 }
 
 /// Layout of [keys] section of config.toml file.
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Default)]
 struct Keys {
     quit: Option<char>,
     back: Option<char>,
@@ -60,6 +68,274 @@ struct Keys {
     increase: Option<char>,
     decrease: Option<char>,
     sort: Option<char>,
+    page_up: Option<char>,
+    page_down: Option<char>,
+    top: Option<char>,
+    bottom: Option<char>,
+    outline: Option<char>,
+    fold: Option<char>,
+    indent: Option<char>,
+    outdent: Option<char>,
+    cut: Option<char>,
+    paste: Option<char>,
+    yank: Option<char>,
+    duplicate: Option<char>,
+    copy_text: Option<char>,
+    mark: Option<char>,
+    mark_all: Option<char>,
+    tag: Option<char>,
+    undo: Option<char>,
+    hide: Option<char>,
+    archive: Option<char>,
+    browse_archive: Option<char>,
+    repeat: Option<char>,
+    timer: Option<char>,
+    estimate: Option<char>,
+    filter: Option<char>,
+    link: Option<char>,
+    actionable: Option<char>,
+    jump: Option<char>,
+    up_levels: Option<char>,
+}
+
+/// Layout of [layout] section of config.toml file.
+#[derive(Deserialize, Debug)]
+struct TomlLayout {
+    split: Option<String>,
+    panels: Option<Vec<String>>,
+    ratios: Option<Vec<usize>>,
+}
+
+/// Layout of [priorities] section of config.toml file.
+#[derive(Deserialize, Debug)]
+struct TomlPriorities {
+    labels: Option<String>,
+    colours: Option<Vec<usize>>,
+}
+
+/// Direction in which the panels between the Parent and Selection panels
+/// are split.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Split {
+    /// Panels side by side, separated by vertical borders.
+    Vertical,
+    /// Panels stacked on top of each other.
+    Horizontal,
+}
+
+/// Panels that can be shown between the Parent and Selection panels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pane {
+    /// Tasks alongside the current task, i.e. the sub-tasks of its parent.
+    Siblings,
+    /// Sub-tasks of the current task.
+    Tasks,
+    /// Preview of the sub-tasks of the selected task.
+    SubTasks,
+    /// Full content of the selected task.
+    Notes,
+    /// Summary of the current task's sub-tasks.
+    Stats,
+    /// Key bindings.
+    Help,
+}
+
+impl Pane {
+    /// Parse a panel name from config.toml.
+    fn from_name(name: &str) -> Option<Pane> {
+        match name {
+            "siblings" => Some(Pane::Siblings),
+            "tasks" => Some(Pane::Tasks),
+            "sub-tasks" => Some(Pane::SubTasks),
+            "notes" => Some(Pane::Notes),
+            "stats" => Some(Pane::Stats),
+            "help" => Some(Pane::Help),
+            _ => None,
+        }
+    }
+
+    /// Title printed on the panel's border.
+    pub fn title(self) -> &'static str {
+        match self {
+            Pane::Siblings => "Siblings",
+            Pane::Tasks => "Tasks",
+            Pane::SubTasks => "Sub-tasks",
+            Pane::Notes => "Notes",
+            Pane::Stats => "Stats",
+            Pane::Help => "Help",
+        }
+    }
+}
+
+/// Text format used when copying tasks to the terminal clipboard.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Clipboard {
+    /// Same format as the save file.
+    Save,
+    /// Markdown checklist.
+    Markdown,
+}
+
+impl Clipboard {
+    /// Parse a clipboard format name from config.toml.
+    fn from_name(name: &str) -> Option<Clipboard> {
+        match name {
+            "save" => Some(Clipboard::Save),
+            "markdown" => Some(Clipboard::Markdown),
+            _ => None,
+        }
+    }
+}
+
+/// Priority levels that tasks can be given, each with a single character
+/// label (used in the save file) and a colour.
+#[derive(Debug, Clone)]
+pub struct Priorities {
+    /// Levels from highest to lowest priority.
+    levels: Vec<(char, Option<usize>)>,
+}
+
+impl Default for Priorities {
+    /// High (A), medium (B) and low (C) priority, in red, yellow and green.
+    fn default() -> Priorities {
+        Priorities {
+            levels: vec![('A', Some(1)), ('B', Some(3)), ('C', Some(2))],
+        }
+    }
+}
+
+impl Priorities {
+    /// Create a priority scheme from the [priorities] section of
+    /// config.toml, falling back to the default if there are no labels.
+    fn from_toml(toml: TomlPriorities) -> Priorities {
+        let labels: Vec<char> = match toml.labels {
+            Some(labels) => labels.chars().filter(|ch| !ch.is_whitespace()).collect(),
+            None => Vec::new(),
+        };
+        if labels.is_empty() {
+            warn!("Priority scheme has no labels.");
+            return Priorities::default();
+        }
+        let colours = toml.colours.unwrap_or_default();
+        let levels = labels
+            .into_iter()
+            .enumerate()
+            .map(|(i, label)| (label, colours.get(i).copied().filter(|&c| c <= 7)))
+            .collect();
+        Priorities { levels }
+    }
+
+    /// Labels of all levels, from highest to lowest priority.
+    pub fn labels(&self) -> impl Iterator<Item = char> + '_ {
+        self.levels.iter().map(|(label, _)| *label)
+    }
+
+    /// Rank of a priority, where 0 is the highest. Labels that aren't in
+    /// the scheme rank below all others, followed by no priority.
+    pub fn rank(&self, priority: Option<char>) -> usize {
+        match priority {
+            Some(label) => self
+                .levels
+                .iter()
+                .position(|(l, _)| *l == label)
+                .unwrap_or(self.levels.len()),
+            None => self.levels.len() + 1,
+        }
+    }
+
+    /// The next priority up from the given one.
+    pub fn increase(&self, priority: Option<char>) -> Option<char> {
+        match self.rank(priority) {
+            0 => priority,
+            rank => self
+                .levels
+                .get(rank.min(self.levels.len()) - 1)
+                .map(|l| l.0),
+        }
+    }
+
+    /// The next priority down from the given one, which is no priority
+    /// after the lowest level.
+    pub fn decrease(&self, priority: Option<char>) -> Option<char> {
+        self.levels.get(self.rank(priority) + 1).map(|l| l.0)
+    }
+
+    /// Colour used to draw tasks with the given priority.
+    pub fn colour(&self, priority: Option<char>) -> Option<usize> {
+        self.levels.get(self.rank(priority)).and_then(|l| l.1)
+    }
+}
+
+/// Arrangement of the panels between the Parent and Selection panels.
+#[derive(Debug, Clone)]
+pub struct Layout {
+    /// Direction of the split.
+    pub split: Split,
+    /// Panels in order (left to right, or top to bottom), with the
+    /// relative amount of space each one takes up.
+    pub panels: Vec<(Pane, usize)>,
+}
+
+impl Default for Layout {
+    /// Tasks and Sub-tasks panels side by side.
+    fn default() -> Layout {
+        Layout {
+            split: Split::Vertical,
+            panels: vec![(Pane::Tasks, 1), (Pane::SubTasks, 1)],
+        }
+    }
+}
+
+impl Layout {
+    /// Create a layout from the [layout] section of config.toml, falling
+    /// back to the default for anything missing or invalid.
+    fn from_toml(toml: TomlLayout) -> Layout {
+        let default = Layout::default();
+
+        let split = match toml.split.as_deref() {
+            Some("vertical") => Split::Vertical,
+            Some("horizontal") => Split::Horizontal,
+            Some(other) => {
+                warn!("Unknown layout split: {}", other);
+                default.split
+            }
+            None => default.split,
+        };
+
+        let mut panels = match toml.panels {
+            Some(names) => {
+                let ratios = toml.ratios.unwrap_or_default();
+                let mut panels = Vec::new();
+                for (i, name) in names.iter().enumerate() {
+                    match Pane::from_name(name) {
+                        Some(pane) => {
+                            let ratio = ratios.get(i).copied().unwrap_or(1).max(1);
+                            panels.push((pane, ratio));
+                        }
+                        None => warn!("Unknown layout panel: {}", name),
+                    }
+                }
+                panels
+            }
+            None => {
+                let mut panels = default.panels;
+                if let Some(ratios) = toml.ratios {
+                    for (panel, ratio) in panels.iter_mut().zip(ratios) {
+                        panel.1 = ratio.max(1);
+                    }
+                }
+                panels
+            }
+        };
+
+        // The Tasks panel can't be hidden
+        if !panels.iter().any(|(pane, _)| *pane == Pane::Tasks) {
+            warn!("Layout is missing the tasks panel.");
+            panels.insert(0, (Pane::Tasks, 1));
+        }
+
+        Layout { split, panels }
+    }
 }
 
 /// Yat's configuration.
@@ -131,17 +407,91 @@ pub struct Config<'a> {
     pub decrease: Key,
     /// Key to sort tasks by priority.
     pub sort: Key,
+    /// Key to move selection up a page.
+    pub page_up: Key,
+    /// Key to move selection down a page.
+    pub page_down: Key,
+    /// Key to move selection to the first task.
+    pub top: Key,
+    /// Key to move selection to the last task.
+    pub bottom: Key,
+    /// Key to toggle the outline view.
+    pub outline: Key,
+    /// Key to fold or unfold a task in the outline view.
+    pub fold: Key,
+    /// Key to make selected task a sub-task of the task above.
+    pub indent: Key,
+    /// Key to make selected task a sibling of its parent.
+    pub outdent: Key,
+    /// Key to cut selected task and its sub-tasks.
+    pub cut: Key,
+    /// Key to paste the cut task after the selected task.
+    pub paste: Key,
+    /// Key to copy the selected task and its sub-tasks.
+    pub yank: Key,
+    /// Key to duplicate the selected task below itself.
+    pub duplicate: Key,
+    /// Key to copy the selected task to the terminal clipboard as text.
+    pub copy_text: Key,
+    /// Key to mark or unmark the selected task for bulk actions.
+    pub mark: Key,
+    /// Key to mark all tasks, or clear the marks.
+    pub mark_all: Key,
+    /// Key to add a tag to the selected or marked tasks.
+    pub tag: Key,
+    /// Key to undo the last change.
+    pub undo: Key,
+    /// Key to show or hide completed tasks.
+    pub hide: Key,
+    /// Key to archive completed tasks.
+    pub archive: Key,
+    /// Key to browse archived tasks.
+    pub browse_archive: Key,
+    /// Set how the selected task recurs.
+    pub repeat: Key,
+    /// Start or stop timing the selected task.
+    pub timer: Key,
+    /// Set the estimated effort of the selected task.
+    pub estimate: Key,
+    /// Filter tasks by their estimated effort.
+    pub filter: Key,
+    /// Make a task depend on another, picked next.
+    pub link: Key,
+    /// Show only tasks that can be done next.
+    pub actionable: Key,
+    /// Jump to any task, searching by its path.
+    pub jump: Key,
+    /// Return focus several levels up at once.
+    pub up_levels: Key,
+
+    /// Layout configuration.
+    pub layout: Layout,
+
+    /// Priority levels.
+    pub priorities: Priorities,
 
     /// Miscellaneous settings
     /// Whether to save on exit
     pub save_on_exit: bool,
     /// Whether to print priority explicity
     pub print_priority: bool,
+    /// Whether to enable mouse support
+    pub mouse: bool,
+    /// Format of text copied to the terminal clipboard
+    pub clipboard: Clipboard,
+    /// Whether to complete parent tasks when all their sub-tasks are complete
+    pub auto_complete: bool,
+    /// Whether to reopen complete parent tasks when a sub-task is not complete
+    pub auto_reopen: bool,
+    /// Whether completed tasks are hidden at start-up
+    pub hide_complete: bool,
+    /// Whether to uncomplete the sub-tasks of the next occurrence of a recurring task
+    pub reset_recurring: bool,
 }
 
-impl<'a> Config<'a> {
+impl Default for Config<'static> {
     /// Create default configuration.
-    pub fn default() -> Config<'static> {
+    fn default() -> Config<'static> {
         // Default border characters
         let hline = "─";
         let vline = "│";
@@ -180,10 +530,50 @@ impl<'a> Config<'a> {
         let increase = Key::Char('>');
         let decrease = Key::Char('<');
         let sort = Key::Char('r');
+        let page_up = Key::PageUp;
+        let page_down = Key::PageDown;
+        let top = Key::Home;
+        let bottom = Key::End;
+        let outline = Key::Char('o');
+        let fold = Key::Char('\t');
+        let indent = Key::Char(']');
+        let outdent = Key::Char('[');
+        let cut = Key::Char('x');
+        let paste = Key::Char('p');
+        let yank = Key::Char('y');
+        let duplicate = Key::Char('D');
+        let copy_text = Key::Char('Y');
+        let mark = Key::Char('m');
+        let mark_all = Key::Char('M');
+        let tag = Key::Char('t');
+        let undo = Key::Char('z');
+        let hide = Key::Char('H');
+        let archive = Key::Char('A');
+        let browse_archive = Key::Char('v');
+        let repeat = Key::Char('R');
+        let timer = Key::Char('T');
+        let estimate = Key::Char('E');
+        let filter = Key::Char('f');
+        let link = Key::Char('L');
+        let actionable = Key::Char('N');
+        let jump = Key::Char('/');
+        let up_levels = Key::Char('B');
+
+        // Default layout
+        let layout = Layout::default();
+
+        // Default priority levels
+        let priorities = Priorities::default();
 
         // Misc
         let save_on_exit = false;
         let print_priority = false;
+        let mouse = false;
+        let clipboard = Clipboard::Save;
+        let auto_complete = false;
+        let auto_reopen = false;
+        let hide_complete = false;
+        let reset_recurring = true;
 
         Config {
             hline,
@@ -217,8 +607,44 @@ impl<'a> Config<'a> {
             increase,
             decrease,
             sort,
+            page_up,
+            page_down,
+            top,
+            bottom,
+            outline,
+            fold,
+            indent,
+            outdent,
+            cut,
+            paste,
+            yank,
+            duplicate,
+            copy_text,
+            mark,
+            mark_all,
+            tag,
+            undo,
+            hide,
+            archive,
+            browse_archive,
+            repeat,
+            timer,
+            estimate,
+            filter,
+            link,
+            actionable,
+            jump,
+            up_levels,
+            layout,
+            priorities,
             save_on_exit,
             print_priority,
+            mouse,
+            clipboard,
+            auto_complete,
+            auto_reopen,
+            hide_complete,
+            reset_recurring,
         }
     }
 }
@@ -256,8 +682,44 @@ pub struct ConfigBuffer {
     pub increase: Option<Key>,
     pub decrease: Option<Key>,
     pub sort: Option<Key>,
+    pub page_up: Option<Key>,
+    pub page_down: Option<Key>,
+    pub top: Option<Key>,
+    pub bottom: Option<Key>,
+    pub outline: Option<Key>,
+    pub fold: Option<Key>,
+    pub indent: Option<Key>,
+    pub outdent: Option<Key>,
+    pub cut: Option<Key>,
+    pub paste: Option<Key>,
+    pub yank: Option<Key>,
+    pub duplicate: Option<Key>,
+    pub copy_text: Option<Key>,
+    pub mark: Option<Key>,
+    pub mark_all: Option<Key>,
+    pub tag: Option<Key>,
+    pub undo: Option<Key>,
+    pub hide: Option<Key>,
+    pub archive: Option<Key>,
+    pub browse_archive: Option<Key>,
+    pub repeat: Option<Key>,
+    pub timer: Option<Key>,
+    pub estimate: Option<Key>,
+    pub filter: Option<Key>,
+    pub link: Option<Key>,
+    pub actionable: Option<Key>,
+    pub jump: Option<Key>,
+    pub up_levels: Option<Key>,
+    pub layout: Option<Layout>,
+    pub priorities: Option<Priorities>,
     pub save_on_exit: Option<bool>,
     pub print_priority: Option<bool>,
+    pub mouse: Option<bool>,
+    pub clipboard: Option<Clipboard>,
+    pub auto_complete: Option<bool>,
+    pub auto_reopen: Option<bool>,
+    pub hide_complete: Option<bool>,
+    pub reset_recurring: Option<bool>,
 }
 
 impl ConfigBuffer {
@@ -323,8 +785,61 @@ impl ConfigBuffer {
         let increase = choose_config_val!(increase, "increase key");
         let decrease = choose_config_val!(decrease, "decrease key");
         let sort = choose_config_val!(sort, "sort key");
+        let page_up = choose_config_val!(page_up, "page_up key");
+        let page_down = choose_config_val!(page_down, "page_down key");
+        let top = choose_config_val!(top, "top key");
+        let bottom = choose_config_val!(bottom, "bottom key");
+        let outline = choose_config_val!(outline, "outline key");
+        let fold = choose_config_val!(fold, "fold key");
+        let indent = choose_config_val!(indent, "indent key");
+        let outdent = choose_config_val!(outdent, "outdent key");
+        let cut = choose_config_val!(cut, "cut key");
+        let paste = choose_config_val!(paste, "paste key");
+        let yank = choose_config_val!(yank, "yank key");
+        let duplicate = choose_config_val!(duplicate, "duplicate key");
+        let copy_text = choose_config_val!(copy_text, "copy_text key");
+        let mark = choose_config_val!(mark, "mark key");
+        let mark_all = choose_config_val!(mark_all, "mark_all key");
+        let tag = choose_config_val!(tag, "tag key");
+        let undo = choose_config_val!(undo, "undo key");
+        let hide = choose_config_val!(hide, "hide key");
+        let archive = choose_config_val!(archive, "archive key");
+        let browse_archive = choose_config_val!(browse_archive, "browse_archive key");
+        let repeat = choose_config_val!(repeat, "repeat key");
+        let timer = choose_config_val!(timer, "timer key");
+        let estimate = choose_config_val!(estimate, "estimate key");
+        let filter = choose_config_val!(filter, "filter key");
+        let link = choose_config_val!(link, "link key");
+        let actionable = choose_config_val!(actionable, "actionable key");
+        let jump = choose_config_val!(jump, "jump key");
+        let up_levels = choose_config_val!(up_levels, "up_levels key");
+        // Layout
+        let layout = match &self.layout {
+            Some(layout) => {
+                info!("Using custom layout.");
+                layout.clone()
+            }
+            None => default.layout,
+        };
+
+        // Priorities
+        let priorities = match &self.priorities {
+            Some(priorities) => {
+                info!("Using custom priorities.");
+                priorities.clone()
+            }
+            None => default.priorities,
+        };
+
+        // Misc
         let save_on_exit = choose_config_val!(save_on_exit, "save_on_exit");
         let print_priority = choose_config_val!(print_priority, "print_priority");
+        let mouse = choose_config_val!(mouse, "mouse");
+        let clipboard = choose_config_val!(clipboard, "clipboard");
+        let auto_complete = choose_config_val!(auto_complete, "auto_complete");
+        let auto_reopen = choose_config_val!(auto_reopen, "auto_reopen");
+        let hide_complete = choose_config_val!(hide_complete, "hide_complete");
+        let reset_recurring = choose_config_val!(reset_recurring, "reset_recurring");
 
         Config {
             hline,
@@ -358,12 +873,73 @@ impl ConfigBuffer {
             increase,
             decrease,
             sort,
+            page_up,
+            page_down,
+            top,
+            bottom,
+            outline,
+            fold,
+            indent,
+            outdent,
+            cut,
+            paste,
+            yank,
+            duplicate,
+            copy_text,
+            mark,
+            mark_all,
+            tag,
+            undo,
+            hide,
+            archive,
+            browse_archive,
+            repeat,
+            timer,
+            estimate,
+            filter,
+            link,
+            actionable,
+            jump,
+            up_levels,
+            layout,
+            priorities,
             save_on_exit,
             print_priority,
+            mouse,
+            clipboard,
+            auto_complete,
+            auto_reopen,
+            hide_complete,
+            reset_recurring,
         }
     }
 }
 
+/// Human-readable name of a key binding.
+pub fn key_name(key: Key) -> String {
+    match key {
+        Key::Char('\n') => String::from("Enter"),
+        Key::Char(' ') => String::from("Space"),
+        Key::Char('\t') => String::from("Tab"),
+        Key::Char(ch) => ch.to_string(),
+        Key::Up => String::from("Up"),
+        Key::Down => String::from("Down"),
+        Key::Left => String::from("Left"),
+        Key::Right => String::from("Right"),
+        Key::PageUp => String::from("PgUp"),
+        Key::PageDown => String::from("PgDn"),
+        Key::Home => String::from("Home"),
+        Key::End => String::from("End"),
+        Key::Backspace => String::from("Bksp"),
+        Key::Delete => String::from("Del"),
+        Key::Esc => String::from("Esc"),
+        Key::F(n) => format!("F{}", n),
+        Key::Ctrl(ch) => format!("^{}", ch),
+        Key::Alt(ch) => format!("M-{}", ch),
+        _ => String::from("?"),
+    }
+}
+
 /// Check for file at ~/.todo/config.toml and if present load
 /// user configuration.
 pub fn check_for_config() -> Option<ConfigBuffer> {
@@ -437,48 +1013,24 @@ pub fn check_for_config() -> Option<ConfigBuffer> {
         None => (None, None, None, None, None, None, None, None, None, None),
     };
 
-    let (
-        quit,
-        back,
-        save,
-        add,
-        edit,
-        delete,
-        task_up,
-        task_down,
-        up,
-        down,
-        focus,
-        complete,
-        increase,
-        decrease,
-        sort,
-    ) = match toml_config.keys {
-        Some(keys) => (
-            keys.quit,
-            keys.back,
-            keys.save,
-            keys.add,
-            keys.edit,
-            keys.delete,
-            keys.task_up,
-            keys.task_down,
-            keys.up,
-            keys.down,
-            keys.focus,
-            keys.complete,
-            keys.increase,
-            keys.decrease,
-            keys.sort,
-        ),
-        None => (
-            None, None, None, None, None, None, None, None, None, None, None, None, None, None,
-            None,
-        ),
-    };
+    let keys = toml_config.keys.unwrap_or_default();
+    let layout = toml_config.layout.map(Layout::from_toml);
+    let priorities = toml_config.priorities.map(Priorities::from_toml);
 
     let save_on_exit = toml_config.save_on_exit;
     let print_priority = toml_config.print_priority;
+    let mouse = toml_config.mouse;
+    let auto_complete = toml_config.auto_complete;
+    let auto_reopen = toml_config.auto_reopen;
+    let hide_complete = toml_config.hide_complete;
+    let reset_recurring = toml_config.reset_recurring;
+    let clipboard = toml_config.clipboard.and_then(|name| {
+        let format = Clipboard::from_name(&name);
+        if format.is_none() {
+            warn!("Unknown clipboard format: {}", name);
+        }
+        format
+    });
 
     Some(ConfigBuffer {
         hline,
@@ -497,22 +1049,109 @@ pub fn check_for_config() -> Option<ConfigBuffer> {
         colour7: colour7.map(|x| color::Rgb(x[0], x[1], x[2])),
         colourfg: colourfg.map(|x| color::Rgb(x[0], x[1], x[2])),
         colourbg: colourbg.map(|x| color::Rgb(x[0], x[1], x[2])),
-        quit: quit.map(Key::Char),
-        back: back.map(Key::Char),
-        save: save.map(Key::Char),
-        add: add.map(Key::Char),
-        edit: edit.map(Key::Char),
-        delete: delete.map(Key::Char),
-        task_up: task_up.map(Key::Char),
-        task_down: task_down.map(Key::Char),
-        up: up.map(Key::Char),
-        down: down.map(Key::Char),
-        focus: focus.map(Key::Char),
-        complete: complete.map(Key::Char),
-        increase: increase.map(Key::Char),
-        decrease: decrease.map(Key::Char),
-        sort: sort.map(Key::Char),
+        quit: keys.quit.map(Key::Char),
+        back: keys.back.map(Key::Char),
+        save: keys.save.map(Key::Char),
+        add: keys.add.map(Key::Char),
+        edit: keys.edit.map(Key::Char),
+        delete: keys.delete.map(Key::Char),
+        task_up: keys.task_up.map(Key::Char),
+        task_down: keys.task_down.map(Key::Char),
+        up: keys.up.map(Key::Char),
+        down: keys.down.map(Key::Char),
+        focus: keys.focus.map(Key::Char),
+        complete: keys.complete.map(Key::Char),
+        increase: keys.increase.map(Key::Char),
+        decrease: keys.decrease.map(Key::Char),
+        sort: keys.sort.map(Key::Char),
+        page_up: keys.page_up.map(Key::Char),
+        page_down: keys.page_down.map(Key::Char),
+        top: keys.top.map(Key::Char),
+        bottom: keys.bottom.map(Key::Char),
+        outline: keys.outline.map(Key::Char),
+        fold: keys.fold.map(Key::Char),
+        indent: keys.indent.map(Key::Char),
+        outdent: keys.outdent.map(Key::Char),
+        cut: keys.cut.map(Key::Char),
+        paste: keys.paste.map(Key::Char),
+        yank: keys.yank.map(Key::Char),
+        duplicate: keys.duplicate.map(Key::Char),
+        copy_text: keys.copy_text.map(Key::Char),
+        mark: keys.mark.map(Key::Char),
+        mark_all: keys.mark_all.map(Key::Char),
+        tag: keys.tag.map(Key::Char),
+        undo: keys.undo.map(Key::Char),
+        hide: keys.hide.map(Key::Char),
+        archive: keys.archive.map(Key::Char),
+        browse_archive: keys.browse_archive.map(Key::Char),
+        repeat: keys.repeat.map(Key::Char),
+        timer: keys.timer.map(Key::Char),
+        estimate: keys.estimate.map(Key::Char),
+        filter: keys.filter.map(Key::Char),
+        link: keys.link.map(Key::Char),
+        actionable: keys.actionable.map(Key::Char),
+        jump: keys.jump.map(Key::Char),
+        up_levels: keys.up_levels.map(Key::Char),
+        layout,
+        priorities,
         save_on_exit,
-        print_priority
+        print_priority,
+        mouse,
+        clipboard,
+        auto_complete,
+        auto_reopen,
+        hide_complete,
+        reset_recurring,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn priorities_rank_in_order() {
+        let priorities = Priorities::default();
+        assert_eq!(priorities.rank(Some('A')), 0);
+        assert_eq!(priorities.rank(Some('C')), 2);
+        // Labels from another scheme rank below the scheme, then no priority
+        assert_eq!(priorities.rank(Some('Z')), 3);
+        assert_eq!(priorities.rank(None), 4);
+    }
+
+    #[test]
+    fn priorities_step_within_scheme() {
+        let priorities = Priorities::default();
+        assert_eq!(priorities.increase(Some('B')), Some('A'));
+        assert_eq!(priorities.decrease(Some('B')), Some('C'));
+        // The highest level stays put, and the lowest drops to no priority
+        assert_eq!(priorities.increase(Some('A')), Some('A'));
+        assert_eq!(priorities.decrease(Some('C')), None);
+        assert_eq!(priorities.decrease(None), None);
+        // No priority and unknown labels go up to the lowest level
+        assert_eq!(priorities.increase(None), Some('C'));
+        assert_eq!(priorities.increase(Some('Z')), Some('C'));
+        assert_eq!(priorities.decrease(Some('Z')), None);
+    }
+
+    #[test]
+    fn priorities_from_labels() {
+        let priorities = Priorities::from_toml(TomlPriorities {
+            labels: Some(String::from("1 2 3 4")),
+            colours: Some(vec![1, 9]),
+        });
+        assert_eq!(priorities.labels().collect::<String>(), "1234");
+        assert_eq!(priorities.colour(Some('1')), Some(1));
+        // Colours out of range, missing, or for labels not in the scheme
+        assert_eq!(priorities.colour(Some('2')), None);
+        assert_eq!(priorities.colour(Some('3')), None);
+        assert_eq!(priorities.colour(Some('A')), None);
+        assert_eq!(priorities.increase(Some('A')), Some('4'));
+
+        let empty = Priorities::from_toml(TomlPriorities {
+            labels: Some(String::from(" ")),
+            colours: None,
+        });
+        assert_eq!(empty.labels().collect::<String>(), "ABC");
+    }
+}
@@ -0,0 +1,304 @@
+//! Sharing the save file with other programs: a lock file while the list is
+//! open, checking for changes made on disk before saving over them, and
+//! reloading the list when it changes on disk.
+use crate::merge;
+use crate::todo::ToDo;
+use crate::View;
+use log::{info, warn};
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::fs::{metadata, read_to_string, remove_file, write, OpenOptions};
+use std::hash::Hasher;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::rc::Rc;
+use std::time::SystemTime;
+
+/// What the save file held at some point, to tell when it has changed.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) struct DiskState {
+    modified: Option<SystemTime>,
+    hash: u64,
+}
+
+/// Hash the contents of a save file.
+fn text_hash(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    hasher.write(text.as_bytes());
+    hasher.finish()
+}
+
+/// Read a file, along with its state on disk.
+fn read_disk(filename: &Path) -> Option<(String, DiskState)> {
+    let modified = metadata(filename).and_then(|m| m.modified()).ok();
+    let text = read_to_string(filename).ok()?;
+    let hash = text_hash(&text);
+    Some((text, DiskState { modified, hash }))
+}
+
+/// Find the state of a file on disk, if it can be read.
+pub(crate) fn disk_state(filename: &Path) -> Option<DiskState> {
+    read_disk(filename).map(|(_, state)| state)
+}
+
+/// Find the lock file of a save file, which sits next to it.
+fn lock_path(filename: &Path) -> Option<PathBuf> {
+    let name = filename.file_name()?.to_string_lossy();
+    Some(filename.with_file_name(format!("{}.lock", name)))
+}
+
+impl<'a> View<'a> {
+    /// Take the lock on the save file. If another instance holds it, ask
+    /// whether to open the list read-only instead.
+    pub(crate) fn lock_save_file(&mut self) {
+        let filename = match self.save_path().as_deref().and_then(lock_path) {
+            Some(filename) if !self.readonly => filename,
+            _ => return,
+        };
+        let created = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&filename);
+        match created {
+            Ok(mut file) => {
+                if let Err(err) = write!(file, "{}", process::id()) {
+                    warn!("Unable to write lock file: {}", err);
+                }
+            }
+            Err(_) if filename.exists() => {
+                let holder = read_to_string(&filename).unwrap_or_default();
+                let prompt = format!(
+                    "The list is open elsewhere (process {}). Open read-only? y/n",
+                    holder.trim()
+                );
+                self.list_tasks();
+                if self.popup(&prompt) {
+                    self.readonly = true;
+                    return;
+                }
+                // Take the lock over, e.g. after a crash left it behind
+                if let Err(err) = write(&filename, process::id().to_string()) {
+                    warn!("Unable to write lock file: {}", err);
+                    return;
+                }
+            }
+            Err(err) => {
+                warn!("Unable to create lock file: {}", err);
+                return;
+            }
+        }
+        self.lock = Some(filename);
+    }
+
+    /// Give up the lock on the save file, if it is held.
+    pub(crate) fn unlock_save_file(&mut self) {
+        let filename = match self.lock.take() {
+            Some(filename) => filename,
+            None => return,
+        };
+        // Another instance may have taken the lock over since
+        let holder = read_to_string(&filename).unwrap_or_default();
+        if holder.trim() != process::id().to_string() {
+            info!("Lock file was taken over by process {}.", holder.trim());
+            return;
+        }
+        if let Err(err) = remove_file(&filename) {
+            warn!("Unable to remove lock file: {}", err);
+        }
+    }
+
+    /// Check if the save file was changed by something else since it was
+    /// last loaded or saved.
+    fn changed_on_disk(&mut self, filename: &Path) -> bool {
+        if let (Some(known), Ok(modified)) =
+            (self.disk, metadata(filename).and_then(|m| m.modified()))
+        {
+            if known.modified == Some(modified) {
+                return false;
+            }
+        }
+        match (self.disk, disk_state(filename)) {
+            (_, None) => false,
+            (None, Some(_)) => true,
+            (Some(known), Some(state)) if known.hash == state.hash => {
+                // Only touched, so remember when
+                self.disk = Some(state);
+                false
+            }
+            (Some(_), Some(_)) => true,
+        }
+    }
+
+    /// Save todo list to file, first asking what to do if the file was
+    /// changed on disk since it was loaded. Returns Err if it wasn't saved.
+    pub(crate) fn save(&mut self) -> Result<(), ()> {
+        if self.readonly {
+            self.status = Some(String::from("Read-only: not saved."));
+            return Err(());
+        }
+        let filename = self.save_path().ok_or(())?;
+        let mut merged = None;
+        if self.changed_on_disk(&filename) {
+            let prompt = "Changed on disk: (r)eload, (o)verwrite, (m)erge or (c)ancel?";
+            match self.choose(prompt, &['r', 'o', 'm', 'c']) {
+                Some('r') => {
+                    self.reload(&filename);
+                    return Err(());
+                }
+                Some('o') => (),
+                Some('m') => merged = Some(self.merge_from_disk(&filename)?),
+                _ => return Err(()),
+            }
+        }
+        let tree = merged.clone().unwrap_or_else(|| self.root_task());
+        if let Err(err) = tree.borrow().save(&filename) {
+            warn!("Unable to save: {}", err);
+            self.status = Some(format!("Unable to save: {}", err));
+            return Err(());
+        }
+        self.disk = disk_state(&filename);
+        self.disk_changed = false;
+        match merged {
+            // Pick up the merged tasks, keeping focus where it was
+            Some(_) => self.reload(&filename),
+            None => self.mark_saved(),
+        }
+        Ok(())
+    }
+
+    /// Remember the todo list as it is now, as saved.
+    pub(crate) fn mark_saved(&mut self) {
+        self.saved = self.root_task().borrow().to_file_string();
+    }
+
+    /// Check if the todo list changed since it was last loaded or saved.
+    fn unsaved_changes(&self) -> bool {
+        self.root_task().borrow().to_file_string() != self.saved
+    }
+
+    /// Reload the todo list if the save file changed on disk, unless that
+    /// would lose changes that haven't been saved yet.
+    pub(crate) fn watch_save_file(&mut self) {
+        let filename = match self.save_path() {
+            Some(filename) if !self.disk_changed => filename,
+            _ => return,
+        };
+        if !self.changed_on_disk(&filename) {
+            return;
+        }
+        // Folding and keeping tasks sorted still change a read-only list,
+        // but it can't be saved, so there is nothing to lose by reloading
+        if self.unsaved_changes() && !self.readonly {
+            warn!("Save file changed on disk, but there are unsaved changes.");
+            self.disk_changed = true;
+        } else {
+            self.reload(&filename);
+        }
+    }
+
+    /// Replace the todo list with the save file on disk, losing any changes
+    /// that weren't saved. Focus and the selection stay on the same tasks
+    /// where they still exist.
+    fn reload(&mut self, filename: &Path) {
+        let (text, state) = match read_disk(filename) {
+            Some(read) => read,
+            None => {
+                warn!("Unable to read save file.");
+                return;
+            }
+        };
+        let root = match ToDo::tree_from_string(&text) {
+            Ok(root) => root,
+            Err(err) => {
+                warn!("Unable to parse save file: {}", err);
+                return;
+            }
+        };
+
+        // The current task and its parents, from the top level down
+        let mut focus = Vec::new();
+        let mut todo = Rc::clone(&self.current_task);
+        loop {
+            let parent = todo.borrow().parent.upgrade();
+            match parent {
+                Some(parent) => {
+                    focus.insert(0, Rc::clone(&todo));
+                    todo = parent;
+                }
+                None => break,
+            }
+        }
+        let selected = self.selected_task();
+        let cursor = self
+            .outline
+            .as_ref()
+            .and_then(|outline| outline.cursor.as_ref())
+            .and_then(|todo| todo.borrow().id.clone());
+        let (selection, offset) = (self.selection, self.offset);
+
+        self.set_tree(Rc::clone(&root));
+        // The current task wherever it is now, or as far down its old path
+        // as still exists
+        let moved = focus.last().and_then(|todo| todo.borrow().id.clone());
+        let target = match moved.and_then(|id| ToDo::find(&root, &id)) {
+            Some(todo) => todo,
+            None => {
+                let mut target = Rc::clone(&root);
+                for todo in focus.iter() {
+                    let index = merge::find_task(todo, &target.borrow().sub_tasks);
+                    match index {
+                        Some(index) => {
+                            let child = Rc::clone(&target.borrow().sub_tasks[index]);
+                            target = child;
+                        }
+                        None => break,
+                    }
+                }
+                target
+            }
+        };
+        self.focus_on(&target);
+        // Select the same task again, or whichever is in its place
+        let sub_tasks = self.current_task.borrow().sub_tasks.clone();
+        let index = selected.and_then(|todo| merge::find_task(&todo, &sub_tasks));
+        self.selection = match (index, selection) {
+            (Some(index), _) => Some(index),
+            _ if sub_tasks.is_empty() => None,
+            (None, Some(index)) => Some(index.min(sub_tasks.len() - 1)),
+            (None, None) => None,
+        };
+        self.offset = offset;
+        if let Some(outline) = self.outline.as_mut() {
+            if let Some(todo) = cursor.and_then(|id| ToDo::find(&root, &id)) {
+                outline.cursor = Some(todo);
+            }
+        }
+
+        self.disk = Some(state);
+        self.disk_changed = false;
+        self.mark_saved();
+        info!("Reloaded save file.");
+    }
+
+    /// Merge the changes made on disk with a copy of the todo list, with the
+    /// list as it was last loaded or saved as the base of a three-way merge.
+    /// The list itself is left as it is until the merged copy is saved.
+    fn merge_from_disk(&mut self, filename: &Path) -> Result<Rc<RefCell<ToDo>>, ()> {
+        let theirs = ToDo::read_tree(filename)?;
+        let base = ToDo::tree_from_string(&self.saved)
+            .map_err(|err| warn!("Unable to parse saved list: {}", err))?;
+        let ours = ToDo::tree_from_string(&self.root_task().borrow().to_file_string())
+            .map_err(|err| warn!("Unable to copy the list: {}", err))?;
+        let conflicts = merge::merge_trees(&base, &ours, &theirs);
+        if conflicts > 0 {
+            self.status = Some(format!(
+                "Merged with {} conflicts, tagged {}.",
+                conflicts,
+                merge::CONFLICT_TAG
+            ));
+        }
+        info!("Merged changes from save file.");
+        Ok(ours)
+    }
+}
@@ -0,0 +1,173 @@
+//! Jumping straight to any task, picked with a fuzzy search over the paths
+//! of all tasks.
+use crate::todo::ToDo;
+use crate::{scroll_offset, View};
+use std::cell::RefCell;
+use std::rc::Rc;
+use termion::event::Key;
+use unicode_width::UnicodeWidthStr;
+
+/// Number of matches shown at most.
+const MAX_MATCHES: usize = 100;
+
+/// A task that can be jumped to.
+struct Candidate {
+    todo: Rc<RefCell<ToDo>>,
+    /// Path of tasks from the top level, separated by ": ".
+    path: String,
+    /// Text searched, which includes the ID of the task.
+    text: String,
+}
+
+/// Collect every task below a task, with their paths.
+fn collect_candidates(todo: &Rc<RefCell<ToDo>>, prefix: &str, candidates: &mut Vec<Candidate>) {
+    for sub_task in todo.borrow().sub_tasks.iter() {
+        let sub_task_ref = sub_task.borrow();
+        let path = format!("{}{}", prefix, sub_task_ref.task);
+        let text = match &sub_task_ref.id {
+            Some(id) => format!("{} {}", path, id),
+            None => path.clone(),
+        };
+        candidates.push(Candidate {
+            todo: Rc::clone(sub_task),
+            path: path.clone(),
+            text: text.to_lowercase(),
+        });
+        collect_candidates(sub_task, &format!("{}: ", path), candidates);
+    }
+}
+
+/// Score how well a query matches some text, if all of the query's
+/// characters appear in order (ignoring case and spaces). Matches at the
+/// start of words and runs of matching characters score highest.
+fn fuzzy_score(query: &str, text: &str) -> Option<usize> {
+    let mut score = 0;
+    let mut chars = text.chars();
+    let mut previous: Option<char> = None;
+    let mut run = false;
+    for wanted in query.chars().filter(|ch| !ch.is_whitespace()) {
+        let wanted = wanted.to_lowercase().next().unwrap_or(wanted);
+        loop {
+            let ch = chars.next()?;
+            let word_start = previous.is_none_or(|p| !p.is_alphanumeric());
+            previous = Some(ch);
+            if ch == wanted {
+                score += 1 + if run { 4 } else { 0 } + if word_start { 2 } else { 0 };
+                run = true;
+                break;
+            }
+            run = false;
+        }
+    }
+    Some(score)
+}
+
+impl<'a> View<'a> {
+    /// Prompt for a task to jump to, searching the paths of all tasks as
+    /// the query is typed.
+    pub(crate) fn jump_prompt(&mut self) {
+        let mut candidates = Vec::new();
+        collect_candidates(&self.root_task(), "", &mut candidates);
+        let mut query = String::new();
+        let mut selection = 0;
+        let mut offset = 0;
+        loop {
+            // Best matches first, with shorter paths breaking ties
+            let mut matches: Vec<(usize, &Candidate)> = candidates
+                .iter()
+                .filter_map(|c| fuzzy_score(&query, &c.text).map(|score| (score, c)))
+                .collect();
+            matches.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.path.len().cmp(&b.1.path.len())));
+            matches.truncate(MAX_MATCHES);
+            selection = selection.min(matches.len().saturating_sub(1));
+            let matches: Vec<&Candidate> = matches.into_iter().map(|(_, c)| c).collect();
+            offset = self.draw_jump(&query, &matches, selection, offset);
+
+            match self.window.getch() {
+                Some(Key::Char('\n')) => {
+                    if let Some(candidate) = matches.get(selection) {
+                        let todo = Rc::clone(&candidate.todo);
+                        match self.outline {
+                            Some(_) => self.outline_reveal(&todo),
+                            None => self.focus_on(&todo),
+                        }
+                    }
+                    return;
+                }
+                Some(Key::Esc) => return,
+                Some(Key::Up) => selection = selection.saturating_sub(1),
+                Some(Key::Down) => selection += 1,
+                Some(Key::Backspace) => {
+                    query.pop();
+                    selection = 0;
+                }
+                Some(Key::Char(ch)) if !ch.is_control() => {
+                    query.push(ch);
+                    selection = 0;
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Display the jump prompt and its matches, returning the new scroll
+    /// offset.
+    fn draw_jump(
+        &mut self,
+        query: &str,
+        matches: &[&Candidate],
+        selection: usize,
+        offset: usize,
+    ) -> usize {
+        if self.too_small() {
+            return offset;
+        }
+        self.window.clear();
+        let panels = self.panels();
+        let selected = matches.get(selection);
+
+        // Parent panel shows the ID of the selected match
+        if let Some(candidate) = selected {
+            let inner = panels.parent.inner();
+            if let Some(id) = &candidate.todo.borrow().id {
+                self.window
+                    .mvprintw(inner.y, inner.x, &format!("ID: {}", id));
+            }
+        }
+        self.window.panel(&panels.parent, "Parent");
+
+        // Matches panel
+        let rect = panels.main;
+        let inner = rect.inner();
+        self.window.panel(&rect, "Go to");
+        let index = selected.map(|_| selection);
+        let offset = scroll_offset(offset, index, inner.height, matches.len());
+        let end = matches.len().min(offset + inner.height);
+        for (y, candidate) in (inner.y..).zip(matches[offset..end].iter()) {
+            self.window.wrap_print(
+                y,
+                inner.x + 2,
+                inner.width.saturating_sub(2),
+                &candidate.path,
+            );
+        }
+        self.more_indicators(&rect, offset, matches.len() - end);
+        if let Some(i) = index {
+            self.window.colour_on(6, 8);
+            self.window.mvprintw(inner.y + i - offset, inner.x, ">");
+            self.window.colour_off();
+        }
+
+        // Query, typed into the Selection panel
+        let inner = panels.selection.inner();
+        self.window.panel(&panels.selection, "Search");
+        self.window
+            .wrap_print(inner.y, inner.x + 1, inner.width.saturating_sub(1), query);
+        let width = UnicodeWidthStr::width(query);
+        self.window
+            .mv(inner.y, (inner.x + 1 + width).min(inner.x + inner.width));
+        self.window.show_cursor();
+        self.window.refresh();
+        offset
+    }
+}
@@ -1,25 +1,55 @@
 /// Functionality for creating todo list using terminal user interface.
+mod archive;
 pub mod config;
+mod disk;
+mod jump;
 pub mod logger;
+pub mod merge;
+mod outline;
+pub mod report;
 mod todo;
 mod tui;
 
+use chrono::Local;
+use config::{Clipboard, Pane};
 use dirs::home_dir;
+use disk::{disk_state, DiskState};
 use log::{info, warn};
+use outline::Outline;
 use std::cell::RefCell;
-use std::env::Args;
 use std::fs::{create_dir, metadata, File};
 use std::io::{self, Read};
 use std::path::PathBuf;
 use std::rc::{Rc, Weak};
-use std::str::Lines;
-use termion::event::Key;
-use todo::{Priority, ToDo};
-use tui::Window;
+use std::time::Duration;
+use termion::event::{Key, MouseButton, MouseEvent};
+use todo::{
+    short_duration, Estimate, EstimateFilter, Recurrence, Snapshot, Sort, SortKey, ToDo, DAY_FORMAT,
+};
+use tui::{Input, Rect, Window};
 use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
+/// Minimum terminal height (rows) needed to display yat.
+const MIN_HEIGHT: usize = 10;
+/// Minimum terminal width (columns) needed to display yat.
+const MIN_WIDTH: usize = 30;
+
+/// Width of the progress bar in the Parent panel.
+const PROGRESS_BAR_WIDTH: usize = 10;
+
+/// Number of changes that can be undone.
+const UNDO_LIMIT: usize = 100;
+
+/// How often the display is refreshed, to keep a running timer up to date
+/// and notice changes to the save file.
+const TIMER_TICK: Duration = Duration::from_secs(1);
+
+/// Message shown when a change is refused because the list is read-only.
+const READ_ONLY_MESSAGE: &str = "Read-only: changes are disabled.";
+
 /// Check if save file exists.
-pub fn look_for_save(mut args: Args) -> Result<PathBuf, ()> {
+#[allow(clippy::result_unit_err)]
+pub fn look_for_save(mut args: impl Iterator<Item = String>) -> Result<PathBuf, ()> {
     args.next();
 
     match args.next() {
@@ -69,72 +99,165 @@ pub fn look_for_save(mut args: Args) -> Result<PathBuf, ()> {
     }
 }
 
+/// Regions of the screen occupied by each panel.
+struct Panels {
+    parent: Rect,
+    selection: Rect,
+    main: Rect,
+    panes: Vec<(Pane, Rect)>,
+}
+
+impl Panels {
+    /// Find the region occupied by a pane, if it is shown.
+    fn get(&self, pane: Pane) -> Option<Rect> {
+        self.panes
+            .iter()
+            .find(|(kind, _)| *kind == pane)
+            .map(|(_, rect)| *rect)
+    }
+}
+
+/// A task that focus has moved below, and where it was in the task.
+struct Frame {
+    task: Rc<RefCell<ToDo>>,
+    selection: Option<usize>,
+    offset: usize,
+}
+
 /// Wrapper around the terminal user interface (Window) and the todo list
 /// tree structure (ToDo).
 pub struct View<'a> {
     window: Window<'a>,
     current_task: Rc<RefCell<ToDo>>,
     selection: Option<usize>,
-    root: bool,
+    offset: usize,
+    /// Tasks above the current task that focus came through, top first.
+    stack: Vec<Frame>,
+    /// Pieces of the path last shown in the Parent panel, with the number
+    /// of levels up each leads to.
+    breadcrumb: Vec<(String, usize)>,
+    /// Message shown in place of the selection until the next input.
+    status: Option<String>,
+    readonly: bool,
+    /// Lock file held on the save file while the list is open.
+    lock: Option<PathBuf>,
+    /// The save file as it was when last loaded or saved.
+    disk: Option<DiskState>,
+    /// The list as it was when last loaded or saved, in save file format.
+    saved: String,
+    /// Whether the save file changed on disk while there were unsaved
+    /// changes, so wasn't reloaded.
+    disk_changed: bool,
     quit: bool,
+    outline: Option<Outline>,
+    clipboard: Option<Rc<RefCell<ToDo>>>,
+    marked: Vec<Rc<RefCell<ToDo>>>,
+    undo: Vec<Snapshot>,
+    hide_complete: bool,
+    estimate_filter: Option<EstimateFilter>,
+    actionable_only: bool,
+    link_from: Option<Rc<RefCell<ToDo>>>,
     save_file: Option<PathBuf>,
 }
 
 impl<'a> View<'a> {
     /// Create view of a new todo list.
+    #[allow(clippy::result_unit_err)]
     pub fn new(config: config::Config<'a>) -> Result<View<'a>, ()> {
         let root = ToDo::new("", Weak::new());
         let stdin = io::stdin();
         let stdout = io::stdout();
         let mut window = Window::new(stdin, stdout, config)?;
         window.colour_off();
+        let hide_complete = window.config.hide_complete;
 
-        info!("Created new View.");
-        Ok(View {
+        let mut view = View {
             window,
             current_task: Rc::new(RefCell::new(root)),
             selection: None,
-            root: true,
+            offset: 0,
+            stack: Vec::new(),
+            breadcrumb: Vec::new(),
+            status: None,
+            readonly: false,
+            lock: None,
+            disk: None,
+            saved: String::new(),
+            disk_changed: false,
             quit: false,
+            outline: None,
+            clipboard: None,
+            marked: Vec::new(),
+            undo: Vec::new(),
+            hide_complete,
+            estimate_filter: None,
+            actionable_only: false,
+            link_from: None,
             save_file: None,
-        })
+        };
+        view.mark_saved();
+
+        info!("Created new View.");
+        Ok(view)
     }
 
     /// Create view of a todo list loaded from save file.
+    #[allow(clippy::result_unit_err)]
     pub fn new_from_save(filename: PathBuf, config: config::Config<'a>) -> Result<View<'a>, ()> {
         let root = ToDo::new("", Weak::new());
         let stdin = io::stdin();
         let stdout = io::stdout();
         let mut window = Window::new(stdin, stdout, config)?;
         window.colour_off();
+        let hide_complete = window.config.hide_complete;
 
         let mut view = View {
             window,
             current_task: Rc::new(RefCell::new(root)),
             selection: None,
-            root: true,
+            offset: 0,
+            stack: Vec::new(),
+            breadcrumb: Vec::new(),
+            status: None,
+            readonly: false,
+            lock: None,
+            disk: None,
+            saved: String::new(),
+            disk_changed: false,
             quit: false,
+            outline: None,
+            clipboard: None,
+            marked: Vec::new(),
+            undo: Vec::new(),
+            hide_complete,
+            estimate_filter: None,
+            actionable_only: false,
+            link_from: None,
             save_file: Some(filename.clone()),
         };
 
-        let proot = Rc::clone(&view.current_task);
-        if let Ok(buf) = Self::load(filename) {
-            match view.fill_children(&mut buf.lines(), 0) {
-                Ok(()) => {
-                    view.current_task = proot;
-                }
-                Err(err) => {
-                    warn!("Unable to parse save file: {}", err);
-                    let new_root = ToDo::new("", Weak::new());
-                    view.current_task = Rc::new(RefCell::new(new_root));
-                }
+        if let Ok(buf) = Self::load(filename.clone()) {
+            match ToDo::tree_from_string(&buf) {
+                Ok(root) => view.set_tree(root),
+                Err(err) => warn!("Unable to parse save file: {}", err),
             }
+            view.disk = disk_state(&filename);
         };
+        view.mark_saved();
 
         info!("Created new View from save file.");
         Ok(view)
     }
 
+    /// Only allow looking at the todo list, refusing any changes to it and
+    /// saving.
+    pub fn set_readonly(&mut self, readonly: bool) {
+        self.readonly = readonly;
+        if readonly {
+            info!("Opened read-only.");
+        }
+    }
+
     /// Load save file into string buffer.
     fn load(filename: PathBuf) -> Result<String, ()> {
         let mut file = match File::open(filename) {
@@ -155,86 +278,174 @@ impl<'a> View<'a> {
         }
     }
 
-    /// Parse save file and load into todo list tree structure.
-    fn fill_children(&mut self, buf: &mut Lines, tabs: usize) -> Result<(), &'static str> {
-        // Parse save file line by line
-        if let Some(line) = buf.next() {
-            // Use indentation to determine where to insert each task. If
-            // indentation is the same as the previous line then we continue
-            // adding sub-tasks to the current line.
-            let num_tabs = tab_num(&line);
-            let current = Rc::clone(&self.current_task);
-            if num_tabs == tabs + 1 {
-                // If indentation is increased compared to the previous line,
-                // then the previously added sub-task is the new current task
-                let n = self.current_task.borrow().sub_tasks.len();
-                if n == 0 {
-                    return Err("Can't have child without parent.");
-                }
-                let new_current = &current.borrow().sub_tasks[n - 1];
-                self.current_task = Rc::clone(&new_current);
-            } else if num_tabs < tabs {
-                // If indentation is decreased compared to the previous line,
-                // then the parent (or an even earlier ancestor) of the
-                // previous task is the new current task
-                self.ancestor(tabs - num_tabs);
-            } else if num_tabs > tabs + 1 {
-                return Err("Too much indentation.");
-            }
-
-            self.add_task_from_string(line.trim_start());
-
-            // Continue onto next line
-            self.fill_children(buf, num_tabs)?;
-        }
-        Ok(())
-    }
-
-    /// Move current task to parent task, if it exists.
-    fn ancestor(&mut self, level: usize) {
-        let current = Rc::clone(&self.current_task);
-        let pparent = &current.borrow().parent;
-        if level > 0 {
-            if let Some(parent) = pparent.upgrade() {
-                self.current_task = Rc::clone(&parent);
-                self.ancestor(level - 1);
-            }
+    /// Replace the todo list with another tree, focusing on its top level.
+    fn set_tree(&mut self, root: Rc<RefCell<ToDo>>) {
+        // In case the file was edited to repeat an ID
+        ToDo::renew_duplicate_ids(&root, &root);
+        self.selection = match root.borrow().sub_tasks.is_empty() {
+            true => None,
+            false => Some(0),
+        };
+        self.current_task = root;
+        self.stack.clear();
+        self.offset = 0;
+        self.marked.clear();
+        self.undo.clear();
+        self.link_from = None;
+        if let Some(outline) = self.outline.as_mut() {
+            outline.cursor = self.current_task.borrow().sub_tasks.first().cloned();
         }
     }
 
     /// Game loop for user interaction and display.
     pub fn run(&mut self) {
+        self.lock_save_file();
         loop {
+            self.watch_save_file();
             self.list_tasks();
-            match self.window.getch() {
+            let input = self.window.getinput_timeout(TIMER_TICK);
+            if input.is_some() {
+                self.status = None;
+            }
+            let key = match input {
+                Some(Input::Key(key)) => Some(key),
+                Some(Input::Mouse(event)) => {
+                    self.mouse(event);
+                    None
+                }
+                _ => None,
+            };
+            match key {
+                Some(key) if self.refuse_change(key) => (),
+                Some(key) if self.outline.is_some() => self.outline_key(key),
                 Some(key) if key == self.window.config.quit => {
                     self.quit = true;
                 }
-                Some(key) if key == self.window.config.back => match self.root {
-                    true => (),
-                    false => break,
-                },
-                Some(key) if key == self.window.config.save => self.save(),
+                Some(key) if key == self.window.config.back => self.back(1),
+                Some(key) if key == self.window.config.save => {
+                    let _ = self.save();
+                }
                 Some(key) if key == self.window.config.add => self.add_task_from_input(),
                 Some(key) if key == self.window.config.edit => self.edit_task(),
                 Some(key) if key == self.window.config.delete => self.remove_task(),
                 Some(key) if key == self.window.config.task_up => self.move_task(true),
                 Some(key) if key == self.window.config.task_down => self.move_task(false),
-                Some(key) if key == self.window.config.focus => self.new_focus(),
+                Some(key) if key == self.window.config.focus => self.new_focus(None),
                 Some(key) if key == self.window.config.complete => self.complete_task(),
                 Some(key) if key == self.window.config.up => self.move_selection(true),
                 Some(key) if key == self.window.config.down => self.move_selection(false),
+                Some(key) if key == self.window.config.page_up => self.move_page(true),
+                Some(key) if key == self.window.config.page_down => self.move_page(false),
+                Some(key) if key == self.window.config.top => self.move_to_end(true),
+                Some(key) if key == self.window.config.bottom => self.move_to_end(false),
                 Some(key) if key == self.window.config.increase => self.increase_priority(),
                 Some(key) if key == self.window.config.decrease => self.decrease_priority(),
-                Some(key) if key == self.window.config.sort => self.sort_by_priority(),
+                Some(key) if key == self.window.config.sort => self.sort_menu(),
+                Some(key) if key == self.window.config.outline => self.toggle_outline(),
+                Some(key) if key == self.window.config.indent => self.indent_task(),
+                Some(key) if key == self.window.config.outdent => self.outdent_task(),
+                Some(key) if key == self.window.config.cut => self.cut_task(),
+                Some(key) if key == self.window.config.paste => self.paste_task(),
+                Some(key) if key == self.window.config.yank => self.yank_task(),
+                Some(key) if key == self.window.config.duplicate => self.duplicate_task(),
+                Some(key) if key == self.window.config.copy_text => self.copy_text(),
+                Some(key) if key == self.window.config.mark => self.mark_task(),
+                Some(key) if key == self.window.config.mark_all => self.mark_all(),
+                Some(key) if key == self.window.config.tag => self.tag_tasks(),
+                Some(key) if key == self.window.config.repeat => self.set_recurrence(),
+                Some(key) if key == self.window.config.timer => self.toggle_timer(),
+                Some(key) if key == self.window.config.estimate => self.set_estimate(),
+                Some(key) if key == self.window.config.filter => self.set_estimate_filter(),
+                Some(key) if key == self.window.config.link => self.link_task(),
+                Some(key) if key == self.window.config.actionable => self.toggle_actionable(),
+                Some(key) if key == self.window.config.jump => self.jump_prompt(),
+                Some(key) if key == self.window.config.up_levels => self.up_levels(),
+                Some(key) if key == self.window.config.undo => self.undo(),
+                Some(key) if key == self.window.config.hide => self.toggle_hide_complete(),
+                Some(key) if key == self.window.config.archive => self.archive_complete(),
+                Some(key) if key == self.window.config.browse_archive => self.browse_archive(),
                 Some(_) => (),
                 None => (),
             }
+            self.keep_sorted();
             if self.quit {
                 self.window.endwin();
                 break;
             }
         }
+        self.unlock_save_file();
+    }
+
+    /// Respond to mouse input: clicking on tasks selects them (or toggles
+    /// their completion if clicking on the checkbox), clicking on sub-tasks
+    /// focuses on them, clicking on the Parent panel returns focus to the
+    /// chosen ancestor, clicking on a sibling moves focus across to it, and
+    /// the scroll-wheel moves the selection.
+    fn mouse(&mut self, event: MouseEvent) {
+        let (button, x, y) = match event {
+            MouseEvent::Press(button, x, y) => (
+                button,
+                (x as usize).saturating_sub(1),
+                (y as usize).saturating_sub(1),
+            ),
+            _ => return,
+        };
+        let (ymax, xmax) = self.window.get_max_yx();
+        if ymax < MIN_HEIGHT || xmax < MIN_WIDTH || self.outline.is_some() {
+            return;
+        }
+        let panels = self.panels();
+        let tasks = panels.get(Pane::Tasks).map(|rect| rect.inner());
+        let sub_tasks = panels.get(Pane::SubTasks).map(|rect| rect.inner());
+        let siblings = panels.get(Pane::Siblings).map(|rect| rect.inner());
+        match button {
+            MouseButton::WheelUp => self.move_selection(true),
+            MouseButton::WheelDown => self.move_selection(false),
+            MouseButton::Left if panels.parent.inner().contains(y, x) => {
+                // Parent panel: find which ancestor in the path was clicked
+                let mut xend = panels.parent.inner().x;
+                for (piece, level) in self.breadcrumb.iter() {
+                    xend += UnicodeWidthStr::width(piece.as_str());
+                    if x < xend {
+                        self.back(*level);
+                        break;
+                    }
+                }
+            }
+            MouseButton::Left if tasks.is_some_and(|rect| rect.contains(y, x)) => {
+                let rect = tasks.unwrap();
+                let visible = self.visible(&self.current_task.borrow());
+                if let Some(&index) = visible.get(self.offset + y - rect.y) {
+                    self.selection = Some(index);
+                    let complete = self.window.config.complete;
+                    if (rect.x + 2..rect.x + 5).contains(&x) && !self.refuse_change(complete) {
+                        self.complete_task();
+                    }
+                }
+            }
+            MouseButton::Left if sub_tasks.is_some_and(|rect| rect.contains(y, x)) => {
+                let rect = sub_tasks.unwrap();
+                if let Some(index) = self.selection {
+                    let selected = Rc::clone(&self.current_task.borrow().sub_tasks[index]);
+                    let visible = self.visible(&selected.borrow());
+                    if let Some(&sub_index) = visible.get(y - rect.y) {
+                        self.new_focus(Some(sub_index));
+                    }
+                }
+            }
+            MouseButton::Left if siblings.is_some_and(|rect| rect.contains(y, x)) => {
+                let rect = siblings.unwrap();
+                let (siblings, offset) = self.siblings(rect.height);
+                if let Some(todo) = siblings.get(offset + y - rect.y) {
+                    if !Rc::ptr_eq(todo, &self.current_task) {
+                        self.back(1);
+                        self.selection = ToDo::index(todo);
+                        self.new_focus(None);
+                    }
+                }
+            }
+            _ => (),
+        }
     }
 
     /// Create a diaglogue for user input with specified prompt.
@@ -261,120 +472,122 @@ impl<'a> View<'a> {
         let plen = UnicodeWidthStr::width(prompt);
         let mut prev_lines: usize = 0;
         loop {
-            let (ymax, xmax) = self.window.get_max_yx();
-            let max_width = if (xmax - 5 - plen) % 2 == 0 {
-                xmax - 4
+            if self.too_small() {
+                prev_lines = 0;
             } else {
-                xmax - 3
-            };
-            let nlines = 1 + (nchars + plen + 1) / max_width;
-            self.window.hide_cursor();
-            self.window
-                .rectangle(' ', (ymax - 2, 1), (nlines, xmax - 2));
+                let (ymax, xmax) = self.window.get_max_yx();
+                let max_width = if xmax.saturating_sub(5 + plen).is_multiple_of(2) {
+                    xmax - 4
+                } else {
+                    xmax - 3
+                };
+                let nlines = 1 + (nchars + plen + 1) / max_width;
+                self.window.hide_cursor();
+                self.window
+                    .rectangle(' ', (ymax - 2, 1), (nlines, xmax - 2));
 
-            // Print dialogue box
-            if nlines != prev_lines {
-                self.window.border((ymax - 1, 0), (2 + nlines, xmax));
-                prev_lines = nlines;
-            }
+                // Print dialogue box
+                if nlines != prev_lines {
+                    self.window.border((ymax - 1, 0), (2 + nlines, xmax));
+                    prev_lines = nlines;
+                }
 
-            // Print prompt
-            self.window.colour_on(0, 7);
-            self.window.mvprintw(ymax - 1 - nlines, 2, prompt);
-            self.window.colour_off();
+                // Print prompt
+                self.window.colour_on(0, 7);
+                self.window
+                    .mvprintw(ymax.saturating_sub(1 + nlines), 2, prompt);
+                self.window.colour_off();
 
-            // Print entry
-            let mut ypos = ymax - 1 - nlines;
-            let mut xpos = plen + 3;
-            for (i, ch) in entry.char_indices() {
-                if let Some(w) = UnicodeWidthChar::width(ch) {
-                    if xpos + w > max_width + 2 {
-                        ypos += 1;
-                        xpos = 2;
-                    }
+                // Print entry
+                let mut ypos = ymax.saturating_sub(1 + nlines);
+                let mut xpos = plen + 3;
+                for (i, ch) in entry.char_indices() {
+                    if let Some(w) = UnicodeWidthChar::width(ch) {
+                        if xpos + w > max_width + 2 {
+                            ypos += 1;
+                            xpos = 2;
+                        }
 
-                    let mut j = i + 1;
-                    while !entry.is_char_boundary(j) && j < entry.len() {
-                        j += 1;
-                    }
+                        let mut j = i + 1;
+                        while !entry.is_char_boundary(j) && j < entry.len() {
+                            j += 1;
+                        }
 
-                    self.window.mvprintw(ypos, xpos, &entry[i..j]);
-                    xpos += w;
+                        self.window.mvprintw(ypos, xpos, &entry[i..j]);
+                        xpos += w;
+                    }
                 }
-            }
 
-            // Print cursor position
-            let y = (chars + plen + 1) / max_width;
-            let x = (chars + plen + 1) % max_width;
-            self.window.show_cursor();
-            self.window.mv(ymax - 1 - nlines + y, 2 + x);
-            self.window.refresh();
+                // Print cursor position
+                let y = (chars + plen + 1) / max_width;
+                let x = (chars + plen + 1) % max_width;
+                self.window.show_cursor();
+                self.window.mv(ymax.saturating_sub(1 + nlines) + y, 2 + x);
+                self.window.refresh();
+            }
 
             // User input
             match self.window.getch() {
+                None => {
+                    // Terminal resized, so redraw everything underneath
+                    self.list_tasks();
+                    prev_lines = 0;
+                }
                 Some(Key::Char('\n')) => break,
-                Some(Key::Char(ch)) => {
+                Some(Key::Char(ch)) if !ch.is_control() => {
                     if index >= entry.len() {
                         entry.push(ch);
                     } else {
                         entry.insert(index, ch);
                     }
                     index += ch.len_utf8();
-                    let chwidth = UnicodeWidthChar::width(ch).unwrap();
+                    let chwidth = UnicodeWidthChar::width(ch).unwrap_or(0);
                     chars += chwidth;
                     nchars += chwidth;
                 }
-                Some(Key::Backspace) => {
-                    if !entry.is_empty() {
-                        let end = index;
-                        while index > 0 {
-                            index -= 1;
-                            if entry.is_char_boundary(index) {
-                                break;
-                            }
+                Some(Key::Backspace) if !entry.is_empty() && index > 0 => {
+                    let end = index;
+                    while index > 0 {
+                        index -= 1;
+                        if entry.is_char_boundary(index) {
+                            break;
                         }
-                        let chwidth = UnicodeWidthStr::width(&entry[index..end]);
-                        chars -= chwidth;
-                        nchars -= chwidth;
-                        entry.remove(index);
                     }
+                    let chwidth = UnicodeWidthStr::width(&entry[index..end]);
+                    chars -= chwidth;
+                    nchars -= chwidth;
+                    entry.remove(index);
                 }
-                Some(Key::Delete) => {
-                    if !entry.is_empty() && index < entry.len() {
-                        let mut end = index;
-                        while end < entry.len() {
-                            end += 1;
-                            if entry.is_char_boundary(end) {
-                                break;
-                            }
+                Some(Key::Delete) if !entry.is_empty() && index < entry.len() => {
+                    let mut end = index;
+                    while end < entry.len() {
+                        end += 1;
+                        if entry.is_char_boundary(end) {
+                            break;
                         }
-                        nchars -= UnicodeWidthStr::width(&entry[index..end]);
-                        entry.remove(index);
                     }
+                    nchars -= UnicodeWidthStr::width(&entry[index..end]);
+                    entry.remove(index);
                 }
-                Some(Key::Left) => {
-                    if index > 0 {
-                        let end = index;
-                        while index > 0 {
-                            index -= 1;
-                            if entry.is_char_boundary(index) {
-                                break;
-                            }
+                Some(Key::Left) if index > 0 => {
+                    let end = index;
+                    while index > 0 {
+                        index -= 1;
+                        if entry.is_char_boundary(index) {
+                            break;
                         }
-                        chars -= UnicodeWidthStr::width(&entry[index..end]);
                     }
+                    chars -= UnicodeWidthStr::width(&entry[index..end]);
                 }
-                Some(Key::Right) => {
-                    if index < entry.len() {
-                        let start = index;
-                        while index < entry.len() {
-                            index += 1;
-                            if entry.is_char_boundary(index) {
-                                break;
-                            }
+                Some(Key::Right) if index < entry.len() => {
+                    let start = index;
+                    while index < entry.len() {
+                        index += 1;
+                        if entry.is_char_boundary(index) {
+                            break;
                         }
-                        chars += UnicodeWidthStr::width(&entry[start..index]);
                     }
+                    chars += UnicodeWidthStr::width(&entry[start..index]);
                 }
                 _ => (),
             }
@@ -382,174 +595,868 @@ impl<'a> View<'a> {
         entry
     }
 
+    /// Find the regions of the screen occupied by each panel.
+    fn panels(&self) -> Panels {
+        let (ymax, xmax) = self.window.get_max_yx();
+        let layout = &self.window.config.layout;
+        let main = Rect {
+            y: 3,
+            x: 0,
+            height: ymax.saturating_sub(6),
+            width: xmax,
+        };
+        let weights: Vec<usize> = layout.panels.iter().map(|(_, ratio)| *ratio).collect();
+        let panes = layout
+            .panels
+            .iter()
+            .map(|(pane, _)| *pane)
+            .zip(main.split(layout.split, &weights))
+            .collect();
+
+        Panels {
+            parent: Rect {
+                y: 0,
+                x: 0,
+                height: 3,
+                width: xmax,
+            },
+            selection: Rect {
+                y: ymax.saturating_sub(3),
+                x: 0,
+                height: 3,
+                width: xmax,
+            },
+            main,
+            panes,
+        }
+    }
+
     /// Display a list of the sub-tasks of the current task.
     fn list_tasks(&mut self) {
+        if self.too_small() {
+            return;
+        }
+        if self.outline.is_some() {
+            self.draw_outline();
+            return;
+        }
         self.window.clear();
         self.window.hide_cursor();
 
-        let (ymax, xmax) = self.window.get_max_yx();
-
-        // Panels
-        let mut path = self.current_task.borrow().task.clone();
-        self.current_task.borrow().task_path(&mut path);
-        self.window.mvprintw(1, 1, &path);
-        self.window.border((2, 0), (3, xmax));
-        self.window.border((ymax - 4, 0), (ymax - 6, xmax / 2));
-        self.window
-            .border((ymax - 4, xmax / 2), (ymax - 6, xmax / 2));
-        self.window.border((ymax - 1, 0), (3, xmax));
-
-        self.window.colour_on(4, 8);
-        self.window.mvprintw(0, 2, "Parent");
-        self.window.mvprintw(3, 2, "Tasks");
-        self.window.mvprintw(3, xmax / 2 + 2, "Sub-tasks");
-        self.window.mvprintw(ymax - 3, 2, "Selection");
-        self.window.colour_off();
-
-        self.window.colour_on(6, 8);
         if let Some(index) = self.selection {
-            if index > self.current_task.borrow().sub_tasks.len() - 1 {
+            if index >= self.current_task.borrow().sub_tasks.len() {
                 warn!("Index larger than it should be.");
                 self.selection = None;
-            } else {
-                self.window.mvprintw(4 + index, 1, ">");
+            }
+        }
+        self.fix_selection();
+
+        let panels = self.panels();
+        let height = self.panel_height();
+        self.scroll(height);
+
+        // Parent panel, with the running timer and a progress bar for the
+        // current task
+        let mut inner = panels.parent.inner();
+        if let Some(running) = ToDo::running_timer(&self.root_task()) {
+            let running = running.borrow();
+            let started = running
+                .started
+                .unwrap_or_else(|| Local::now().naive_local());
+            let seconds = (Local::now().naive_local() - started).num_seconds();
+            let time = format!(
+                "{}:{:02}:{:02}",
+                seconds / 3600,
+                seconds / 60 % 60,
+                seconds % 60
+            );
+            // Room for the timer symbol, the task (truncated if needed) and
+            // the time
+            let name_width = UnicodeWidthStr::width(running.task.as_str());
+            let task_width = (name_width + 3).min(inner.width / 4);
+            let shown = match name_width + 3 <= task_width {
+                true => name_width + 1,
+                false => task_width + 1,
+            };
+            let width = 2 + shown + time.len();
+            if task_width > 3 && inner.width > width + MIN_WIDTH / 2 {
+                let x = inner.x + inner.width - width;
+                self.window.colour_on(5, 8);
+                self.window.mvprintw(inner.y, x, "⏱");
+                self.window.colour_off();
+                self.window
+                    .wrap_print(inner.y, x + 2, task_width, &running.task);
+                self.window.mvprintw(inner.y, x + 2 + shown, &time);
+                inner.width -= width + 1;
+            }
+        }
+        let (total, complete) = self.current_task.borrow().count();
+        let label = format!("{}/{}", complete, total);
+        let bar_width = PROGRESS_BAR_WIDTH + label.len() + 2;
+        if total > 0 && inner.width > bar_width + MIN_WIDTH / 2 {
+            let filled = PROGRESS_BAR_WIDTH * complete / total;
+            let x = inner.x + inner.width - bar_width;
+            self.window.colour_on(2, 8);
+            self.window.mvprintw(inner.y, x, &"█".repeat(filled));
+            self.window.colour_on(0, 8);
+            self.window.mvprintw(
+                inner.y,
+                x + filled,
+                &"░".repeat(PROGRESS_BAR_WIDTH - filled),
+            );
+            self.window.colour_off();
+            self.window
+                .mvprintw(inner.y, x + PROGRESS_BAR_WIDTH + 1, &label);
+            inner.width = x - inner.x;
+        }
+        self.breadcrumb = Self::make_breadcrumb(&self.current_task, inner.width.saturating_sub(3));
+        let path: String = self
+            .breadcrumb
+            .iter()
+            .map(|(piece, _)| piece.as_str())
+            .collect();
+        self.window.wrap_print(inner.y, inner.x, inner.width, &path);
+        self.window.panel(&panels.parent, &self.parent_title());
+
+        // Selection panel, or a status message
+        self.window.panel(&panels.selection, "Selection");
+        match (self.status.clone(), self.selection) {
+            (Some(status), _) => self.draw_status(&panels.selection, &status),
+            (None, Some(index)) => {
+                let inner = panels.selection.inner();
+                self.window.colour_on(6, 8);
                 self.window.wrap_print(
-                    ymax - 2,
-                    2,
-                    xmax - 3,
+                    inner.y,
+                    inner.x + 1,
+                    inner.width.saturating_sub(1),
                     &self.current_task.borrow().sub_tasks[index].borrow().task,
                 );
+                self.window.colour_off();
             }
-        };
+            (None, None) => (),
+        }
+
+        for (pane, rect) in panels.panes.iter() {
+            // Note any filters, or a pending dependency, in the title
+            let mut notes = Vec::new();
+            if let Some(filter) = &self.estimate_filter {
+                notes.push(filter.to_string());
+            }
+            if self.actionable_only {
+                notes.push(String::from("actionable"));
+            }
+            if self.link_from.is_some() {
+                notes.push(String::from("pick dependency"));
+            }
+            match pane {
+                Pane::Tasks if !notes.is_empty() => {
+                    let title = format!("{} ({})", pane.title(), notes.join(", "));
+                    self.window.panel(rect, &title);
+                }
+                _ => self.window.panel(rect, pane.title()),
+            }
+            match pane {
+                Pane::Siblings => self.draw_siblings(rect),
+                Pane::Tasks => self.draw_tasks(rect),
+                Pane::SubTasks => self.draw_sub_tasks(rect),
+                Pane::Notes => self.draw_notes(rect),
+                Pane::Stats => self.draw_stats(rect),
+                Pane::Help => self.draw_help(rect),
+            }
+        }
+        self.window.refresh();
+    }
+
+    /// Title of the Parent panel, which notes when the list is read-only or
+    /// has changed on disk.
+    fn parent_title(&self) -> String {
+        let mut notes = Vec::new();
+        if self.readonly {
+            notes.push("read-only");
+        }
+        if self.disk_changed {
+            notes.push("changed on disk");
+        }
+        match notes.is_empty() {
+            true => String::from("Parent"),
+            false => format!("Parent ({})", notes.join(", ")),
+        }
+    }
+
+    /// Print a status message in a panel, in place of its contents.
+    fn draw_status(&mut self, rect: &Rect, status: &str) {
+        let inner = rect.inner();
+        self.window.colour_on(1, 8);
+        self.window
+            .wrap_print(inner.y, inner.x + 1, inner.width.saturating_sub(1), status);
         self.window.colour_off();
+    }
 
-        let sub_tasks = &self.current_task.borrow().sub_tasks;
-        let mut y = 4;
-        for (i, elem) in sub_tasks.iter().enumerate() {
-            if elem.borrow().complete {
-                self.window.mvprintw(y, 3, "[");
-                self.window.colour_on(4, 8);
-                self.window.mvprintw(y, 4, "X");
-                self.window.colour_off();
-                self.window.mvprintw(y, 5, "]");
+    /// Split the path of a task into pieces for the Parent panel, each with
+    /// the number of levels up it leads to. Tasks in the middle of the path
+    /// are replaced by "…" until it fits within width.
+    fn make_breadcrumb(todo: &Rc<RefCell<ToDo>>, width: usize) -> Vec<(String, usize)> {
+        let mut pieces = vec![(todo.borrow().task.clone(), 0)];
+        let mut parent = todo.borrow().parent.upgrade();
+        while let Some(todo) = parent {
+            let piece = format!("{}: ", todo.borrow().task);
+            pieces.insert(0, (piece, pieces[0].1 + 1));
+            parent = todo.borrow().parent.upgrade();
+        }
+        let path_width = |pieces: &[(String, usize)]| -> usize {
+            pieces
+                .iter()
+                .map(|(piece, _)| UnicodeWidthStr::width(piece.as_str()))
+                .sum()
+        };
+        // Keep the root, the top-level task and the current task, hiding
+        // the tasks after the top level first
+        let mut elided = false;
+        while path_width(&pieces) > width {
+            let first = if elided { 3 } else { 2 };
+            if pieces.len() <= first + 1 {
+                break;
+            }
+            let (_, level) = pieces.remove(first);
+            if elided {
+                pieces[2].1 = level;
             } else {
-                self.window.mvprintw(y, 3, "[ ]");
+                pieces.insert(2, (String::from("…: "), level));
+                elided = true;
             }
-            match elem.borrow().priority {
-                Some(Priority::Low) => {
-                    self.window.colour_on(2, 8);
-                }
-                Some(Priority::Medium) => {
-                    self.window.colour_on(3, 8);
-                }
-                Some(Priority::High) => {
-                    self.window.colour_on(1, 8);
-                }
-                _ => (),
-            };
+        }
+        pieces
+    }
+
+    /// Find the tasks alongside the current task that are shown in the
+    /// Siblings panel, and the scroll offset that keeps the current task in
+    /// view.
+    fn siblings(&self, height: usize) -> (Vec<Rc<RefCell<ToDo>>>, usize) {
+        let parent = match self.current_task.borrow().parent.upgrade() {
+            Some(parent) => parent,
+            None => return (Vec::new(), 0),
+        };
+        let siblings: Vec<_> = parent
+            .borrow()
+            .sub_tasks
+            .iter()
+            .filter(|todo| Rc::ptr_eq(todo, &self.current_task) || self.is_shown(&todo.borrow()))
+            .cloned()
+            .collect();
+        let index = siblings
+            .iter()
+            .position(|todo| Rc::ptr_eq(todo, &self.current_task));
+        let offset = scroll_offset(0, index, height, siblings.len());
+        (siblings, offset)
+    }
+
+    /// Draw the Siblings panel, showing the tasks alongside the current task
+    /// with the current task marked.
+    fn draw_siblings(&mut self, rect: &Rect) {
+        let inner = rect.inner();
+        let (siblings, offset) = self.siblings(inner.height);
+        let end = siblings.len().min(offset + inner.height);
+        for (y, todo) in (inner.y..).zip(siblings[offset..end].iter()) {
+            self.print_task(
+                y,
+                inner.x + 2,
+                inner.width.saturating_sub(3),
+                &todo.borrow(),
+            );
+            if Rc::ptr_eq(todo, &self.current_task) {
+                self.window.colour_on(6, 8);
+                self.window.mvprintw(y, inner.x, ">");
+                self.window.colour_off();
+            }
+        }
+        self.more_indicators(rect, offset, siblings.len() - end);
+    }
+
+    /// Draw the Tasks panel, showing only those sub-tasks that fit.
+    fn draw_tasks(&mut self, rect: &Rect) {
+        let inner = rect.inner();
+        let current = Rc::clone(&self.current_task);
+        let sub_tasks = &current.borrow().sub_tasks;
+        let visible = self.visible(&current.borrow());
+        let end = visible.len().min(self.offset + inner.height);
+        for (y, &index) in (inner.y..).zip(visible[self.offset..end].iter()) {
+            let elem = &sub_tasks[index];
+            self.print_task(
+                y,
+                inner.x + 2,
+                inner.width.saturating_sub(3),
+                &elem.borrow(),
+            );
+            if self.marked.iter().any(|todo| Rc::ptr_eq(todo, elem)) {
+                self.window.colour_on(5, 8);
+                self.window.mvprintw(y, inner.x + 1, "*");
+                self.window.colour_off();
+            }
+        }
+        self.more_indicators(rect, self.offset, visible.len() - end);
+
+        if let Some(row) = self.selected_row(&visible) {
+            self.window.colour_on(6, 8);
             self.window
-                .wrap_print(y, 7, xmax / 2 - 8, &elem.borrow().task.to_string());
+                .mvprintw(inner.y + row - self.offset, inner.x, ">");
             self.window.colour_off();
-            y += 1;
-
-            if let Some(index) = self.selection {
-                if index == i {
-                    let mut yy = 4;
-                    for sub_elem in elem.borrow().sub_tasks.iter() {
-                        if sub_elem.borrow().complete {
-                            self.window.mvprintw(yy, xmax / 2 + 3, "[");
-                            self.window.colour_on(4, 8);
-                            self.window.mvprintw(yy, xmax / 2 + 4, "X");
-                            self.window.colour_off();
-                            self.window.mvprintw(yy, xmax / 2 + 5, "]");
-                        } else {
-                            self.window.mvprintw(yy, xmax / 2 + 3, "[ ]");
-                        }
-                        match sub_elem.borrow().priority {
-                            Some(Priority::Low) => {
-                                self.window.colour_on(2, 8);
-                            }
-                            Some(Priority::Medium) => {
-                                self.window.colour_on(3, 8);
-                            }
-                            Some(Priority::High) => {
-                                self.window.colour_on(1, 8);
-                            }
-                            _ => (),
-                        };
-                        self.window.wrap_print(
-                            yy,
-                            xmax / 2 + 7,
-                            xmax / 2 - 8,
-                            &sub_elem.borrow().task.to_string(),
-                        );
-                        self.window.colour_off();
-                        yy += 1;
-                    }
-                }
-            };
         }
+    }
+
+    /// Draw the Sub-tasks panel, previewing the sub-tasks of the selection.
+    fn draw_sub_tasks(&mut self, rect: &Rect) {
+        if let Some(index) = self.selection {
+            let inner = rect.inner();
+            let selected = Rc::clone(&self.current_task.borrow().sub_tasks[index]);
+            let sub_tasks = &selected.borrow().sub_tasks;
+            let visible = self.visible(&selected.borrow());
+            let end = visible.len().min(inner.height);
+            for (y, &index) in (inner.y..).zip(visible[..end].iter()) {
+                self.print_task(
+                    y,
+                    inner.x + 2,
+                    inner.width.saturating_sub(3),
+                    &sub_tasks[index].borrow(),
+                );
+            }
+            self.more_indicators(rect, 0, visible.len() - end);
+        }
+    }
+
+    /// Draw the Notes panel, showing the full content of the selection.
+    fn draw_notes(&mut self, rect: &Rect) {
+        if let Some(index) = self.selection {
+            let inner = rect.inner();
+            let todo = Rc::clone(&self.current_task.borrow().sub_tasks[index]);
+            let todo = todo.borrow();
+            let width = inner.width.saturating_sub(2);
+            let mut lines = wrap_lines(&todo.task, width);
+            if let Some(id) = &todo.id {
+                lines.push(String::new());
+                lines.push(format!("ID: {}", id));
+            }
+            // Followed by the tasks it depends on
+            let root = self.root_task();
+            let dependencies: Vec<_> = todo
+                .depends
+                .iter()
+                .filter_map(|id| ToDo::find(&root, id))
+                .collect();
+            if !dependencies.is_empty() {
+                lines.push(String::new());
+                lines.push(String::from("Depends on:"));
+            }
+            for dependency in dependencies {
+                let dependency = dependency.borrow();
+                let mut path = dependency.task.clone();
+                dependency.task_path(&mut path);
+                let check = if dependency.complete { "[X]" } else { "[ ]" };
+                lines.extend(wrap_lines(
+                    &format!("{} {}", check, path.trim_start_matches(": ")),
+                    width,
+                ));
+            }
+            for (y, line) in (inner.y..).zip(lines.iter().take(inner.height)) {
+                self.window.mvprintw(y, inner.x + 1, line);
+            }
+        }
+    }
+
+    /// Draw the Stats panel, summarising the sub-tasks of the current task.
+    fn draw_stats(&mut self, rect: &Rect) {
+        let inner = rect.inner();
+        let current = self.current_task.borrow();
+        let ntasks = current.sub_tasks.len();
+        let ncomplete = current
+            .sub_tasks
+            .iter()
+            .filter(|todo| todo.borrow().complete)
+            .count();
+        let (total, total_complete) = current.count();
+        let mut lines = vec![format!("Complete: {}/{}", ncomplete, ntasks)];
+        for label in self.window.config.priorities.labels() {
+            let npriority = current
+                .sub_tasks
+                .iter()
+                .filter(|todo| todo.borrow().priority == Some(label))
+                .count();
+            // Only list unused levels when there are few of them
+            if npriority > 0 || self.window.config.priorities.labels().count() <= 3 {
+                lines.push(format!("({}): {}", label, npriority));
+            }
+        }
+        lines.push(format!("All: {}/{}", total_complete, total));
+        let (effort, remaining) = current.effort();
+        if !effort.is_zero() {
+            lines.push(format!("Effort: {} of {} left", remaining, effort));
+        }
+        drop(current);
+
+        for (y, line) in (inner.y..).zip(lines.iter().take(inner.height)) {
+            self.window
+                .wrap_print(y, inner.x + 1, inner.width.saturating_sub(1), line);
+        }
+    }
+
+    /// Draw the Help panel, listing the key bindings.
+    fn draw_help(&mut self, rect: &Rect) {
+        let inner = rect.inner();
+        let config = &self.window.config;
+        let bindings = [
+            (config.add, "add task"),
+            (config.edit, "edit task"),
+            (config.delete, "delete task"),
+            (config.complete, "complete task"),
+            (config.focus, "focus"),
+            (config.back, "back"),
+            (config.task_up, "move task up"),
+            (config.task_down, "move task down"),
+            (config.indent, "indent task"),
+            (config.outdent, "outdent task"),
+            (config.cut, "cut task"),
+            (config.paste, "paste task"),
+            (config.yank, "copy task"),
+            (config.duplicate, "duplicate task"),
+            (config.copy_text, "copy as text"),
+            (config.mark, "mark task"),
+            (config.mark_all, "mark all"),
+            (config.tag, "tag tasks"),
+            (config.repeat, "set recurrence"),
+            (config.timer, "start/stop timer"),
+            (config.estimate, "set estimate"),
+            (config.filter, "filter by estimate"),
+            (config.link, "link dependency"),
+            (config.actionable, "show actionable"),
+            (config.jump, "jump to task"),
+            (config.up_levels, "go up levels"),
+            (config.undo, "undo"),
+            (config.hide, "hide completed"),
+            (config.archive, "archive completed"),
+            (config.browse_archive, "browse archive"),
+            (config.increase, "increase priority"),
+            (config.decrease, "decrease priority"),
+            (config.sort, "sort tasks"),
+            (config.save, "save"),
+            (config.quit, "quit"),
+        ];
+        let lines: Vec<String> = bindings
+            .iter()
+            .map(|(key, action)| format!("{:<6}{}", config::key_name(*key), action))
+            .collect();
+
+        for (y, line) in (inner.y..).zip(lines.iter().take(inner.height)) {
+            self.window
+                .wrap_print(y, inner.x + 1, inner.width.saturating_sub(1), line);
+        }
+    }
+
+    /// Check whether the terminal is too small to display yat, in which
+    /// case a message is displayed instead.
+    fn too_small(&mut self) -> bool {
+        let (ymax, xmax) = self.window.get_max_yx();
+        if ymax >= MIN_HEIGHT && xmax >= MIN_WIDTH {
+            return false;
+        }
+
+        let message = "Terminal too small!";
+        let len = UnicodeWidthStr::width(message);
+        self.window.clear();
+        self.window.hide_cursor();
+        self.window.colour_on(1, 8);
+        if len <= xmax {
+            self.window.mvprintw(ymax / 2, (xmax - len) / 2, message);
+        } else {
+            self.window.wrap_print(ymax / 2, 0, xmax, message);
+        }
+        self.window.colour_off();
         self.window.refresh();
+        true
+    }
+
+    /// Print a single task at row y, column x (zero-indexed), showing its
+    /// completion and colouring its content by priority.
+    fn print_task(&mut self, y: usize, x: usize, width: usize, todo: &ToDo) {
+        // Due date and progress of the sub-tasks, at the right edge
+        let mut width = width;
+        let mut labels = Vec::new();
+        let blocked = todo.is_blocked(&self.root_task());
+        let spent = todo.time_spent(None);
+        if todo.started.is_some() {
+            labels.push(format!("⏱ {}", short_duration(spent)));
+        } else if spent.num_minutes() > 0 {
+            labels.push(short_duration(spent));
+        }
+        if blocked {
+            labels.push(String::from("blocked"));
+        }
+        let (effort, remaining) = todo.effort();
+        if todo.sub_tasks.is_empty() && !effort.is_zero() {
+            labels.push(format!("~{}", effort));
+        } else if !effort.is_zero() {
+            labels.push(format!("~{}/{}", remaining, effort));
+        }
+        if let Some(due) = todo.due {
+            let repeat = if todo.recurrence.is_some() {
+                "↻ "
+            } else {
+                ""
+            };
+            labels.push(format!("{}{}", repeat, due.format(DAY_FORMAT)));
+        } else if todo.recurrence.is_some() {
+            labels.push(String::from("↻"));
+        }
+        if !todo.sub_tasks.is_empty() {
+            let (total, complete) = todo.count();
+            labels.push(format!("{}/{}", complete, total));
+        }
+        if !labels.is_empty() {
+            let label = labels.join(" ");
+            let label_width = UnicodeWidthStr::width(label.as_str());
+            if width > label_width + MIN_WIDTH / 2 {
+                self.window.colour_on(0, 8);
+                self.window.mvprintw(y, x + width + 1 - label_width, &label);
+                self.window.colour_off();
+                width -= label_width + 1;
+            }
+        }
+
+        if todo.complete {
+            self.window.mvprintw(y, x, "[");
+            self.window.colour_on(4, 8);
+            self.window.mvprintw(y, x + 1, "X");
+            self.window.colour_off();
+            self.window.mvprintw(y, x + 2, "]");
+        } else {
+            self.window.mvprintw(y, x, "[ ]");
+        }
+        // Blocked tasks are greyed out
+        if blocked {
+            self.window.colour_on(0, 8);
+        } else if let Some(colour) = self.window.config.priorities.colour(todo.priority) {
+            self.window.colour_on(colour, 8);
+        }
+        self.window
+            .wrap_print(y, x + 4, width.saturating_sub(3), &todo.task);
+        self.window.colour_off();
+    }
+
+    /// Print "more above/below" indicators on the top and bottom borders of
+    /// a panel.
+    fn more_indicators(&mut self, rect: &Rect, above: usize, below: usize) {
+        let xend = rect.x + rect.width;
+        self.window.colour_on(5, 8);
+        if above > 0 {
+            let text = format!(" ↑ {} more ", above);
+            let x = xend.saturating_sub(2 + UnicodeWidthStr::width(text.as_str()));
+            self.window.mvprintw(rect.y, x.max(rect.x), &text);
+        }
+        if below > 0 {
+            let text = format!(" ↓ {} more ", below);
+            let x = xend.saturating_sub(2 + UnicodeWidthStr::width(text.as_str()));
+            self.window
+                .mvprintw(rect.y + rect.height - 1, x.max(rect.x), &text);
+        }
+        self.window.colour_off();
+    }
+
+    /// Number of rows available for tasks inside the Tasks panel.
+    fn panel_height(&self) -> usize {
+        self.panels()
+            .get(Pane::Tasks)
+            .map_or(0, |rect| rect.inner().height)
+    }
+
+    /// Adjust the scroll offset of the Tasks panel so that the selection
+    /// remains visible.
+    fn scroll(&mut self, height: usize) {
+        let visible = self.visible(&self.current_task.borrow());
+        let row = self.selected_row(&visible);
+        self.offset = scroll_offset(self.offset, row, height, visible.len());
+    }
+
+    /// Whether a task is shown, or hidden because it is complete, doesn't
+    /// match the estimate filter or can't be done yet.
+    fn is_shown(&self, todo: &ToDo) -> bool {
+        let matches = match &self.estimate_filter {
+            Some(filter) => filter.matches(todo),
+            None => true,
+        };
+        let actionable = || !todo.complete && !todo.is_blocked(&self.root_task());
+        matches && !(self.hide_complete && todo.complete) && (!self.actionable_only || actionable())
+    }
+
+    /// Show only the tasks that can be done next, or all tasks again.
+    fn toggle_actionable(&mut self) {
+        self.actionable_only = !self.actionable_only;
+    }
+
+    /// Indices of the sub-tasks of a task that are shown.
+    fn visible(&self, todo: &ToDo) -> Vec<usize> {
+        (0..todo.sub_tasks.len())
+            .filter(|&index| self.is_shown(&todo.sub_tasks[index].borrow()))
+            .collect()
+    }
+
+    /// Row of the selected task amongst the visible tasks.
+    fn selected_row(&self, visible: &[usize]) -> Option<usize> {
+        self.selection
+            .and_then(|index| visible.iter().position(|&i| i == index))
+    }
+
+    /// Show or hide completed tasks.
+    fn toggle_hide_complete(&mut self) {
+        self.hide_complete = !self.hide_complete;
+    }
+
+    /// Find the root of the todo list tree.
+    fn root_task(&self) -> Rc<RefCell<ToDo>> {
+        let mut root = Rc::clone(&self.current_task);
+        loop {
+            let parent = root.borrow().parent.upgrade();
+            match parent {
+                Some(parent) => root = parent,
+                None => return root,
+            }
+        }
     }
 
-    /// Increase the priority of the currently selected task.
+    /// Indices of the tasks that actions apply to: the marked tasks if
+    /// there are any, otherwise the selected task.
+    fn targets(&self) -> Vec<usize> {
+        let current = self.current_task.borrow();
+        let marked: Vec<usize> = current
+            .sub_tasks
+            .iter()
+            .enumerate()
+            .filter(|(_, todo)| self.is_shown(&todo.borrow()))
+            .filter(|(_, todo)| self.marked.iter().any(|mark| Rc::ptr_eq(mark, todo)))
+            .map(|(index, _)| index)
+            .collect();
+        match (marked.is_empty(), self.selection) {
+            (false, _) => marked,
+            (true, Some(index)) => vec![index],
+            (true, None) => Vec::new(),
+        }
+    }
+
+    /// Increase the priority of the selected or marked tasks.
     fn increase_priority(&mut self) {
-        if let Some(index) = self.selection {
-            let current = self.current_task.borrow();
+        let targets = self.targets();
+        if targets.is_empty() {
+            return;
+        }
+        self.checkpoint(Rc::clone(&self.current_task));
+        let current = self.current_task.borrow();
+        for index in targets {
             let mut sub_task = current.sub_tasks[index].borrow_mut();
-            sub_task.priority = match sub_task.priority {
-                None => Some(Priority::Low),
-                Some(Priority::Low) => Some(Priority::Medium),
-                Some(Priority::Medium) => Some(Priority::High),
-                Some(Priority::High) => Some(Priority::High),
-            };
+            sub_task.priority = self.window.config.priorities.increase(sub_task.priority);
         }
     }
 
-    /// Decrease the priority of the currently selected task.
+    /// Decrease the priority of the selected or marked tasks.
     fn decrease_priority(&mut self) {
-        if let Some(index) = self.selection {
-            let current = self.current_task.borrow();
+        let targets = self.targets();
+        if targets.is_empty() {
+            return;
+        }
+        self.checkpoint(Rc::clone(&self.current_task));
+        let current = self.current_task.borrow();
+        for index in targets {
             let mut sub_task = current.sub_tasks[index].borrow_mut();
-            sub_task.priority = match sub_task.priority {
-                None => None,
-                Some(Priority::Low) => None,
-                Some(Priority::Medium) => Some(Priority::Low),
-                Some(Priority::High) => Some(Priority::Medium),
-            };
+            sub_task.priority = self.window.config.priorities.decrease(sub_task.priority);
         }
     }
 
     /// Add new task from user input.
     fn add_task_from_input(&mut self) {
         let task = self.input_dialogue("New Task:");
+        self.checkpoint(Rc::clone(&self.current_task));
         let parent = Rc::downgrade(&self.current_task);
         let todo = ToDo::new(&task, parent);
-        let sub_tasks = &mut self.current_task.borrow_mut().sub_tasks;
-        sub_tasks.push(Rc::new(RefCell::new(todo)));
-        self.selection = Some(sub_tasks.len() - 1);
+        let todo = Rc::new(RefCell::new(todo));
+        let ntasks = {
+            let sub_tasks = &mut self.current_task.borrow_mut().sub_tasks;
+            sub_tasks.push(Rc::clone(&todo));
+            sub_tasks.len()
+        };
+        ToDo::renew_duplicate_ids(&todo, &self.root_task());
+        self.selection = Some(ntasks - 1);
+        self.roll_up();
     }
 
-    /// Add new task from string buffer.
-    fn add_task_from_string(&mut self, input: &str) {
-        let parent = Rc::downgrade(&self.current_task);
-        let todo = ToDo::from_string(input, parent);
-        let sub_tasks = &mut self.current_task.borrow_mut().sub_tasks;
-        sub_tasks.push(Rc::new(RefCell::new(todo)));
-        self.selection = Some(sub_tasks.len() - 1);
+    /// Mark the selected or marked tasks as completed, or as not completed
+    /// if they all already are.
+    fn complete_task(&mut self) {
+        let targets = self.targets();
+        if targets.is_empty() {
+            return;
+        }
+        self.checkpoint(Rc::clone(&self.current_task));
+        {
+            let sub_tasks = &self.current_task.borrow().sub_tasks;
+            let complete = !targets
+                .iter()
+                .all(|&index| sub_tasks[index].borrow().complete);
+            for &index in targets.iter() {
+                sub_tasks[index].borrow_mut().set_complete(complete);
+            }
+        }
+        // Recurring tasks that were completed come round again, just after
+        let reset = self.window.config.reset_recurring;
+        let mut current = self.current_task.borrow_mut();
+        let mut spawned = Vec::new();
+        for &index in targets.iter().rev() {
+            if let Some(next) = ToDo::next_occurrence(&current.sub_tasks[index], reset) {
+                current.sub_tasks.insert(index + 1, Rc::clone(&next));
+                spawned.push(next);
+                if let Some(selection) = self.selection.filter(|&s| s > index) {
+                    self.selection = Some(selection + 1);
+                }
+            }
+        }
+        drop(current);
+        for next in spawned {
+            ToDo::renew_duplicate_ids(&next, &self.root_task());
+        }
+        self.roll_up();
     }
 
-    /// Mark task as completed.
-    fn complete_task(&mut self) {
-        let sub_tasks = &mut self.current_task.borrow_mut().sub_tasks;
-        if let Some(index) = self.selection {
-            let mut sub_task = sub_tasks[index].borrow_mut();
-            sub_task.complete = !sub_task.complete;
+    /// Start timing the selected task, stopping any other timer, or stop
+    /// its timer if it is running.
+    fn toggle_timer(&mut self) {
+        let todo = match self.selected_task() {
+            Some(todo) => todo,
+            None => return,
+        };
+        let running = ToDo::running_timer(&self.root_task());
+        if let Some(running) = &running {
+            running.borrow_mut().stop_timer();
+        }
+        if !running.is_some_and(|running| Rc::ptr_eq(&running, &todo)) {
+            todo.borrow_mut().start_timer();
+        }
+    }
+
+    /// Pick the selected task as one that will depend on another, or if one
+    /// has already been picked, make it depend on the selected task. Linking
+    /// the same tasks again removes the dependency.
+    fn link_task(&mut self) {
+        let todo = match self.selected_task() {
+            Some(todo) => todo,
+            None => return,
+        };
+        let from = match self.link_from.take() {
+            Some(from) if !Rc::ptr_eq(&from, &todo) => from,
+            Some(_) => {
+                self.status = Some(String::from("A task can't depend on itself."));
+                return;
+            }
+            None => {
+                self.link_from = Some(todo);
+                return;
+            }
+        };
+        let root = self.root_task();
+        if ToDo::waits_for(&todo.borrow(), &from, &root) {
+            self.status = Some(String::from(
+                "Unable to add dependency, as it would form a cycle.",
+            ));
+            return;
+        }
+        let id = match todo.borrow().id.clone() {
+            Some(id) => id,
+            None => return,
+        };
+        self.checkpoint(Rc::clone(&from));
+        let mut from = from.borrow_mut();
+        match from.depends.iter().position(|dependency| *dependency == id) {
+            Some(index) => {
+                from.depends.remove(index);
+            }
+            None => from.depends.push(id),
         }
     }
 
-    /// Change ordering of sub-tasks for current task.
+    /// Set or clear the estimated effort of the selected task.
+    fn set_estimate(&mut self) {
+        let todo = match self.selected_task() {
+            Some(todo) => todo,
+            None => return,
+        };
+        let estimate = match todo.borrow().estimate {
+            Some(estimate) => estimate.to_string(),
+            None => String::new(),
+        };
+        let input = self.dialogue("Estimate (e.g. 2h, 3pt):", &estimate);
+        let estimate = match input.trim() {
+            "" => None,
+            input => match Estimate::from_name(input) {
+                Some(estimate) => Some(estimate),
+                None => {
+                    self.status = Some(format!("Unknown estimate: {}", input));
+                    return;
+                }
+            },
+        };
+        self.checkpoint(Rc::clone(&self.current_task));
+        todo.borrow_mut().estimate = estimate;
+    }
+
+    /// Show only the tasks whose estimated effort matches a filter, or
+    /// clear the filter.
+    fn set_estimate_filter(&mut self) {
+        let filter = match &self.estimate_filter {
+            Some(filter) => filter.to_string(),
+            None => String::new(),
+        };
+        let input = self.dialogue("Filter by estimate (e.g. <=2h, >3pt, none):", &filter);
+        self.estimate_filter = match input.trim() {
+            "" => None,
+            input => match EstimateFilter::from_name(input) {
+                Some(filter) => Some(filter),
+                None => {
+                    self.status = Some(format!("Unknown estimate filter: {}", input));
+                    return;
+                }
+            },
+        };
+    }
+
+    /// Set or clear the recurrence rule of the selected task.
+    fn set_recurrence(&mut self) {
+        let todo = match self.selected_task() {
+            Some(todo) => todo,
+            None => return,
+        };
+        let rule = match &todo.borrow().recurrence {
+            Some(recurrence) => recurrence.to_string().replace('-', " "),
+            None => String::new(),
+        };
+        let input = self.dialogue("Repeat (daily, weekly mon thu, monthly, after 3):", &rule);
+        let recurrence = match input.trim() {
+            "" => None,
+            input => match Recurrence::from_name(input) {
+                Some(recurrence) => Some(recurrence),
+                None => {
+                    self.status = Some(format!("Unknown rule: {}", input));
+                    return;
+                }
+            },
+        };
+        self.checkpoint(Rc::clone(&self.current_task));
+        todo.borrow_mut().recurrence = recurrence;
+    }
+
+    /// Change ordering of sub-tasks for current task. Marked tasks move
+    /// together, stopping at the ends of the list.
     fn move_task(&mut self, up: bool) {
+        if !self.marked.is_empty() {
+            self.move_marked(up);
+            return;
+        }
+        if self.selection.is_some() {
+            self.checkpoint(Rc::clone(&self.current_task));
+        }
         let sub_tasks = &mut self.current_task.borrow_mut().sub_tasks;
         if let Some(index) = self.selection {
             if up {
@@ -572,28 +1479,372 @@ impl<'a> View<'a> {
         }
     }
 
-    /// Focus on currently selected sub-task.
-    fn new_focus(&mut self) {
-        let previous_root = self.root;
-        let previous_selection = self.selection;
-        let psub_tasks = Rc::clone(&self.current_task);
-        let sub_tasks = &psub_tasks.borrow().sub_tasks;
+    /// Move the marked tasks up or down by one place.
+    fn move_marked(&mut self, up: bool) {
+        let targets = self.targets();
+        let ntasks = self.current_task.borrow().sub_tasks.len();
+        match (targets.first(), targets.last()) {
+            (Some(0), _) if up => return,
+            (_, Some(&last)) if !up && last + 1 == ntasks => return,
+            (None, _) => return,
+            _ => (),
+        }
+        self.checkpoint(Rc::clone(&self.current_task));
+        let selected = self
+            .selection
+            .map(|index| Rc::clone(&self.current_task.borrow().sub_tasks[index]));
+        let sub_tasks = &mut self.current_task.borrow_mut().sub_tasks;
+        if up {
+            for index in targets {
+                sub_tasks.swap(index - 1, index);
+            }
+        } else {
+            for index in targets.into_iter().rev() {
+                sub_tasks.swap(index, index + 1);
+            }
+        }
+        if let Some(selected) = selected {
+            self.selection = sub_tasks
+                .iter()
+                .position(|todo| Rc::ptr_eq(todo, &selected));
+        }
+    }
+
+    /// Make the selected task a sub-task of the task above it.
+    fn indent_task(&mut self) {
+        if let Some(index) = self.selection.filter(|&index| index > 0) {
+            self.checkpoint(Rc::clone(&self.current_task));
+            let sub_task = Rc::clone(&self.current_task.borrow().sub_tasks[index]);
+            if ToDo::indent(&sub_task) {
+                self.selection = Some(index - 1);
+            }
+        }
+    }
+
+    /// Move the selected task out of the current task, to become the next
+    /// sibling of the current task.
+    fn outdent_task(&mut self) {
         if let Some(index) = self.selection {
-            // Focus on sub-task
-            let sub_task = &sub_tasks[index];
-            self.current_task = Rc::clone(sub_task);
-            self.root = false;
+            let parent = self.current_task.borrow().parent.upgrade();
+            if let Some(parent) = parent {
+                self.checkpoint(parent);
+            }
+            let sub_task = Rc::clone(&self.current_task.borrow().sub_tasks[index]);
+            if ToDo::outdent(&sub_task) {
+                let ntasks = self.current_task.borrow().sub_tasks.len();
+                self.selection = match ntasks {
+                    0 => None,
+                    _ => Some(index.min(ntasks - 1)),
+                };
+            }
+        }
+    }
+
+    /// Remove the selected task and its sub-tasks, keeping them to be
+    /// pasted elsewhere. Replaces any task that was already cut.
+    fn cut_task(&mut self) {
+        if let Some(index) = self.selection {
+            self.checkpoint(Rc::clone(&self.current_task));
+            let sub_task = self.current_task.borrow_mut().sub_tasks.remove(index);
+            sub_task.borrow_mut().parent = Weak::new();
+            self.clipboard = Some(sub_task);
+            let ntasks = self.current_task.borrow().sub_tasks.len();
+            self.selection = match ntasks {
+                0 => None,
+                _ => Some(index.min(ntasks - 1)),
+            };
+            self.roll_up();
+        }
+    }
+
+    /// Copy the selected task and its sub-tasks, to be pasted elsewhere.
+    fn yank_task(&mut self) {
+        if let Some(index) = self.selection {
+            let sub_task = Rc::clone(&self.current_task.borrow().sub_tasks[index]);
+            self.clipboard = Some(sub_task.borrow().deep_clone(Weak::new()));
+        }
+    }
+
+    /// Paste a copy of the cut or copied task after the selected task (or
+    /// at the end of the list if there is no selection).
+    fn paste_task(&mut self) {
+        if let Some(todo) = self.clipboard.clone() {
+            let parent = Rc::downgrade(&self.current_task);
+            self.insert_after_selection(todo.borrow().deep_clone(parent));
+        }
+    }
+
+    /// Insert a copy of the selected task and its sub-tasks below it.
+    fn duplicate_task(&mut self) {
+        if let Some(index) = self.selection {
+            let sub_task = Rc::clone(&self.current_task.borrow().sub_tasks[index]);
+            let parent = Rc::downgrade(&self.current_task);
+            self.insert_after_selection(sub_task.borrow().deep_clone(parent));
+        }
+    }
+
+    /// Insert a task after the selected task and select it.
+    fn insert_after_selection(&mut self, todo: Rc<RefCell<ToDo>>) {
+        self.checkpoint(Rc::clone(&self.current_task));
+        let index = {
+            let sub_tasks = &mut self.current_task.borrow_mut().sub_tasks;
+            let index = match self.selection {
+                Some(index) => index + 1,
+                None => sub_tasks.len(),
+            };
+            sub_tasks.insert(index, Rc::clone(&todo));
+            index
+        };
+        ToDo::renew_duplicate_ids(&todo, &self.root_task());
+        self.selection = Some(index);
+        self.roll_up();
+    }
+
+    /// Copy the selected task and its sub-tasks to the system clipboard as
+    /// text, in the format chosen in the config.
+    fn copy_text(&mut self) {
+        if let Some(index) = self.selection {
+            let text = {
+                let current = self.current_task.borrow();
+                let sub_task = current.sub_tasks[index].borrow();
+                match self.window.config.clipboard {
+                    Clipboard::Save => sub_task.to_save_string(),
+                    Clipboard::Markdown => {
+                        let mut buffer = String::new();
+                        sub_task.to_markdown(0, &mut buffer);
+                        buffer
+                    }
+                }
+            };
+            self.window.copy_to_clipboard(&text);
+        }
+    }
+
+    /// Mark or unmark the selected task, then move the selection down.
+    fn mark_task(&mut self) {
+        if let Some(index) = self.selection {
+            let sub_task = Rc::clone(&self.current_task.borrow().sub_tasks[index]);
+            match self
+                .marked
+                .iter()
+                .position(|todo| Rc::ptr_eq(todo, &sub_task))
+            {
+                Some(position) => {
+                    self.marked.remove(position);
+                }
+                None => self.marked.push(sub_task),
+            }
+            let ntasks = self.current_task.borrow().sub_tasks.len();
+            if index + 1 < ntasks {
+                self.selection = Some(index + 1);
+            }
+        }
+    }
+
+    /// Mark every task, or clear the marks if any tasks are marked.
+    fn mark_all(&mut self) {
+        if self.marked.is_empty() {
+            let current = self.current_task.borrow();
+            let visible = self.visible(&current);
+            self.marked = visible
+                .iter()
+                .map(|&index| Rc::clone(&current.sub_tasks[index]))
+                .collect();
+        } else {
+            self.marked.clear();
+        }
+    }
+
+    /// Add a `#tag` to the end of the selected or marked tasks.
+    fn tag_tasks(&mut self) {
+        let targets = self.targets();
+        if targets.is_empty() {
+            return;
+        }
+        let input = self.input_dialogue("Tag:");
+        let tag = format!("#{}", input.trim().trim_start_matches('#'));
+        if tag.len() == 1 || tag.contains(' ') {
+            return;
+        }
+        self.checkpoint(Rc::clone(&self.current_task));
+        let sub_tasks = &self.current_task.borrow().sub_tasks;
+        for index in targets {
+            let mut sub_task = sub_tasks[index].borrow_mut();
+            if !sub_task.task.split_whitespace().any(|word| word == tag) {
+                sub_task.task.push(' ');
+                sub_task.task.push_str(&tag);
+            }
+        }
+    }
+
+    /// Update the completion of the current task and its ancestors, if
+    /// enabled in the config.
+    fn roll_up(&self) {
+        let config = &self.window.config;
+        if config.auto_complete || config.auto_reopen {
+            ToDo::roll_up(&self.current_task, config.auto_complete, config.auto_reopen);
+        }
+    }
+
+    /// Record the sub-tasks of a task before changing them, so that the
+    /// change can be undone.
+    fn checkpoint(&mut self, parent: Rc<RefCell<ToDo>>) {
+        if self.undo.len() == UNDO_LIMIT {
+            self.undo.remove(0);
+        }
+        self.undo.push(Snapshot::new(&parent));
+    }
+
+    /// Undo the last change. A change made above or outside the current task
+    /// is undone from the task it was made in, so that focus never rests on a
+    /// task that is no longer in the tree.
+    fn undo(&mut self) {
+        let parent = match self.undo.last() {
+            Some(snapshot) => Rc::clone(snapshot.parent()),
+            None => {
+                self.status = Some(String::from("Nothing to undo."));
+                return;
+            }
+        };
+        let mut todo = Some(Rc::clone(&parent));
+        let below_current = loop {
+            match todo {
+                Some(t) if Rc::ptr_eq(&t, &self.current_task) => break true,
+                Some(t) => todo = t.borrow().parent.upgrade(),
+                None => break false,
+            }
+        };
+        if !below_current {
+            self.focus_on(&parent);
+            if !Rc::ptr_eq(&parent, &self.current_task) {
+                self.status = Some(String::from(
+                    "Can't undo: the last change was made in a task that is gone.",
+                ));
+                return;
+            }
+        }
+        if let Some(snapshot) = self.undo.pop() {
+            snapshot.restore();
+        }
+        let ntasks = self.current_task.borrow().sub_tasks.len();
+        self.selection = match (self.selection, ntasks) {
+            (_, 0) => None,
+            (Some(index), _) => Some(index.min(ntasks - 1)),
+            (None, _) => None,
+        };
+    }
+
+    /// Check if a key would change the todo list while it is read-only, in
+    /// which case it is refused with a message.
+    fn refuse_change(&mut self, key: Key) -> bool {
+        if !self.readonly {
+            return false;
+        }
+        let config = &self.window.config;
+        let changes = [
+            config.add,
+            config.edit,
+            config.delete,
+            config.task_up,
+            config.task_down,
+            config.complete,
+            config.increase,
+            config.decrease,
+            config.sort,
+            config.indent,
+            config.outdent,
+            config.cut,
+            config.paste,
+            config.duplicate,
+            config.tag,
+            config.repeat,
+            config.timer,
+            config.estimate,
+            config.link,
+            config.undo,
+            config.archive,
+        ];
+        if !changes.contains(&key) {
+            return false;
+        }
+        self.status = Some(String::from(READ_ONLY_MESSAGE));
+        true
+    }
+
+    /// Focus on currently selected sub-task, optionally selecting one of
+    /// its own sub-tasks (the first is selected by default).
+    fn new_focus(&mut self, sub_selection: Option<usize>) {
+        if let Some(index) = self.selection {
+            let sub_task = Rc::clone(&self.current_task.borrow().sub_tasks[index]);
+            let previous_task = std::mem::replace(&mut self.current_task, sub_task);
+            self.stack.push(Frame {
+                task: previous_task,
+                selection: self.selection,
+                offset: self.offset,
+            });
+            self.offset = 0;
+            self.marked.clear();
             self.selection = if !self.current_task.borrow().sub_tasks.is_empty() {
-                Some(0)
+                sub_selection.or(Some(0))
             } else {
                 None
             };
-            self.run();
+        }
+    }
 
-            // Return to parent task (unwrap cannot panic here)
-            self.current_task = sub_task.borrow().parent.upgrade().unwrap();
-            self.root = previous_root;
-            self.selection = previous_selection;
+    /// Return focus to the task a number of levels above the current task,
+    /// as it was when focus left it. Focus stops at the root.
+    fn back(&mut self, levels: usize) {
+        for _ in 0..levels {
+            let frame = match self.stack.pop() {
+                Some(frame) => frame,
+                None => break,
+            };
+            let sub_task = Rc::clone(&self.current_task);
+            self.current_task = sub_task.borrow().parent.upgrade().unwrap_or(frame.task);
+            self.selection = frame.selection;
+            self.offset = frame.offset;
+            self.marked.clear();
+        }
+    }
+
+    /// Ask how many levels to return focus up, going all the way to the top
+    /// level if none are given.
+    fn up_levels(&mut self) {
+        let input = self.input_dialogue("Levels up (empty for the top level):");
+        let levels = match input.trim() {
+            "" => self.stack.len(),
+            input => match input.parse() {
+                Ok(levels) => levels,
+                Err(_) => {
+                    self.status = Some(format!("Not a number of levels: {}", input));
+                    return;
+                }
+            },
+        };
+        self.back(levels);
+    }
+
+    /// Focus on any task in the tree, as though focus had moved down to it
+    /// one level at a time, so that returning to each parent works as usual.
+    fn focus_on(&mut self, todo: &Rc<RefCell<ToDo>>) {
+        let mut path = vec![Rc::clone(todo)];
+        loop {
+            let parent = path.last().unwrap().borrow().parent.upgrade();
+            match parent {
+                Some(parent) => path.push(parent),
+                None => break,
+            }
+        }
+        // The task isn't in the tree any more
+        if !Rc::ptr_eq(path.last().unwrap(), &self.root_task()) {
+            return;
+        }
+        self.back(self.stack.len());
+        path.pop();
+        while let Some(child) = path.pop() {
+            self.selection = ToDo::index(&child);
+            self.new_focus(None);
         }
     }
 
@@ -601,109 +1852,226 @@ impl<'a> View<'a> {
     fn edit_task(&mut self) {
         if let Some(index) = self.selection {
             let task = self.edit_dialogue("Edit Task:", index);
+            self.checkpoint(Rc::clone(&self.current_task));
             let current_task = self.current_task.borrow_mut();
             let mut sub_task = current_task.sub_tasks[index].borrow_mut();
             sub_task.task = task;
         }
     }
 
-    /// Move selection cursor.
+    /// Move selection cursor, wrapping at the ends.
     fn move_selection(&mut self, ifup: bool) {
-        self.selection = if let Some(index) = self.selection {
-            if ifup {
-                self.up(index)
-            } else {
-                self.down(index)
-            }
-        } else {
-            match self.current_task.borrow().sub_tasks.len() {
-                0 => None,
-                _ => Some(0),
-            }
+        let visible = self.visible(&self.current_task.borrow());
+        let ntasks = visible.len();
+        let row = match self.selected_row(&visible) {
+            _ if ntasks == 0 => None,
+            Some(row) if ifup => Some((row + ntasks - 1) % ntasks),
+            Some(row) => Some((row + 1) % ntasks),
+            None => Some(0),
         };
+        self.selection = row.map(|row| visible[row]);
     }
 
-    /// Change index (wrapping below).
-    fn up(&self, index: usize) -> Option<usize> {
-        let ntasks = self.current_task.borrow().sub_tasks.len();
-        if index as isize - 1 < 0 {
-            Some(index + ntasks - 1)
-        } else {
-            Some(index - 1)
+    /// Move selection cursor by a full page of the Tasks panel, without
+    /// wrapping.
+    fn move_page(&mut self, ifup: bool) {
+        let visible = self.visible(&self.current_task.borrow());
+        if visible.is_empty() {
+            return;
         }
+        let height = self.panel_height().max(1);
+        let row = self.selected_row(&visible).unwrap_or(0);
+        let row = if ifup {
+            row.saturating_sub(height)
+        } else {
+            (row + height).min(visible.len() - 1)
+        };
+        self.selection = Some(visible[row]);
     }
 
-    /// Change index (wrapping above).
-    fn down(&self, index: usize) -> Option<usize> {
-        let ntasks = self.current_task.borrow().sub_tasks.len();
-        if index + 1 >= ntasks {
-            Some(index + 1 - ntasks)
+    /// Move selection cursor to the first or last task.
+    fn move_to_end(&mut self, iftop: bool) {
+        let visible = self.visible(&self.current_task.borrow());
+        self.selection = if iftop {
+            visible.first().copied()
         } else {
-            Some(index + 1)
+            visible.last().copied()
+        };
+    }
+
+    /// Make sure the selection is on a visible task, moving it to the next
+    /// one shown (or the previous one, at the end of the list) if not.
+    fn fix_selection(&mut self) {
+        if let Some(index) = self.selection {
+            let visible = self.visible(&self.current_task.borrow());
+            if !visible.contains(&index) {
+                self.selection = visible
+                    .iter()
+                    .find(|&&i| i > index)
+                    .or_else(|| visible.last())
+                    .copied();
+            }
         }
     }
 
     /// Create a pop-up diaglogue with user choice.
     fn popup(&mut self, prompt: &str) -> bool {
-        let (ymax, xmax) = self.window.get_max_yx();
-        self.window.border((ymax - 1, 0), (3, xmax));
-        self.window.rectangle(' ', (ymax - 2, 1), (1, xmax - 2));
-        self.window.colour_on(1, 7);
-        self.window.mvprintw(ymax - 2, 2, prompt);
-        self.window.colour_off();
-        self.window.refresh();
+        self.choose(prompt, &['y', 'n']) == Some('y')
+    }
 
-        let mut choice = false;
+    /// Create a pop-up dialogue for picking one of several choices, each
+    /// made by pressing its key. Returns None if cancelled.
+    fn choose(&mut self, prompt: &str, choices: &[char]) -> Option<char> {
         loop {
+            if !self.too_small() {
+                let (ymax, xmax) = self.window.get_max_yx();
+                self.window.border((ymax - 1, 0), (3, xmax));
+                self.window.rectangle(' ', (ymax - 2, 1), (1, xmax - 2));
+                self.window.colour_on(1, 7);
+                self.window.wrap_print(ymax - 2, 2, xmax - 3, prompt);
+                self.window.colour_off();
+                self.window.refresh();
+            }
+
             match self.window.getch() {
-                Some(Key::Char('y')) => {
-                    choice = true;
-                    break;
-                }
-                Some(Key::Char('n')) => break,
-                Some(Key::Char('q')) => break,
-                Some(Key::Char('b')) => break,
+                Some(Key::Char(ch)) if choices.contains(&ch) => return Some(ch),
+                Some(Key::Char('q')) => return None,
+                Some(Key::Char('b')) => return None,
+                Some(Key::Esc) => return None,
+                None => self.list_tasks(),
                 _ => (),
             }
         }
-        choice
     }
 
     /// Remove selected sub-task.
     fn remove_task(&mut self) {
-        if let Some(index) = self.selection {
-            if self.popup("Are you sure you want to delete this task? y/n") {
-                let mut current_task = self.current_task.borrow_mut();
-                current_task.sub_tasks.remove(index);
-                self.selection = None;
+        let targets = self.targets();
+        let prompt = match targets.len() {
+            0 => return,
+            1 => String::from("Are you sure you want to delete this task? y/n"),
+            n => format!("Are you sure you want to delete these {} tasks? y/n", n),
+        };
+        if self.popup(&prompt) {
+            self.checkpoint(Rc::clone(&self.current_task));
+            for index in targets.into_iter().rev() {
+                self.current_task.borrow_mut().sub_tasks.remove(index);
             }
+            self.marked.clear();
+            self.selection = None;
+            self.roll_up();
         }
     }
 
-    /// Save todo list to file.
-    fn save(&self) {
-        let current = self.current_task.borrow();
-        let filename = match self.save_file.clone() {
-            Some(f) => f,
+    /// Find the file to save the todo list to.
+    fn save_path(&self) -> Option<PathBuf> {
+        match self.save_file.clone() {
+            Some(f) => Some(f),
             None => {
                 let mut buffer = match home_dir() {
                     Some(dir) => dir,
                     None => {
                         warn!("Unable to locate home directory.");
-                        return;
+                        return None;
                     }
                 };
                 buffer.push(".todo/save.txt");
-                buffer
+                Some(buffer)
+            }
+        }
+    }
+
+    /// Choose how to sort the sub-tasks of the current task from a menu.
+    fn sort_menu(&mut self) {
+        let mut reverse = false;
+        let mut recursive = false;
+        let mut keep = false;
+        let key = loop {
+            if !self.too_small() {
+                let (ymax, xmax) = self.window.get_max_yx();
+                let check = |on: bool| if on { "[X]" } else { "[ ]" };
+                let lines = [
+                    String::from(
+                        "Sort by (p)riority, (c)ompletion, (a)lphabetical, (d)ate created, (e)stimate",
+                    ),
+                    format!(
+                        "{} (r)everse  {} (s)ub-tasks too  {} (k)eep sorted  (o)ff",
+                        check(reverse),
+                        check(recursive),
+                        check(keep)
+                    ),
+                ];
+                self.window.border((ymax - 1, 0), (4, xmax));
+                self.window.rectangle(' ', (ymax - 2, 1), (2, xmax - 2));
+                self.window.colour_on(1, 7);
+                for (y, line) in (ymax - 3..).zip(lines.iter()) {
+                    self.window.wrap_print(y, 2, xmax - 3, line);
+                }
+                self.window.colour_off();
+                self.window.refresh();
+            }
+
+            match self.window.getch() {
+                Some(Key::Char('r')) => reverse = !reverse,
+                Some(Key::Char('s')) => recursive = !recursive,
+                Some(Key::Char('k')) => keep = !keep,
+                Some(Key::Char('p')) => break Some(SortKey::Priority),
+                Some(Key::Char('c')) => break Some(SortKey::Completion),
+                Some(Key::Char('a')) => break Some(SortKey::Alphabetical),
+                Some(Key::Char('d')) => break Some(SortKey::Created),
+                Some(Key::Char('e')) => break Some(SortKey::Estimate),
+                Some(Key::Char('o')) => break None,
+                Some(Key::Char('q')) | Some(Key::Char('b')) | Some(Key::Esc) => return,
+                None => self.list_tasks(),
+                _ => (),
             }
         };
 
-        current.save(filename.as_path())
+        self.checkpoint(Rc::clone(&self.current_task));
+        let selected = self.selected_task();
+        let mut current = self.current_task.borrow_mut();
+        match key {
+            Some(key) => {
+                let sort = Sort { key, reverse };
+                current.sort(sort, recursive, &self.window.config.priorities);
+                if keep {
+                    current.set_keep_sorted(Some(sort), recursive);
+                }
+            }
+            None => current.set_keep_sorted(None, recursive),
+        }
+        drop(current);
+        self.select_task(selected);
     }
 
-    /// Sort sub-tasks by priority.
-    fn sort_by_priority(&mut self) {
-        self.current_task.borrow_mut().sort_by_priority()
+    /// Re-sort the current task's sub-tasks if they are kept sorted.
+    fn keep_sorted(&mut self) {
+        let sort = self.current_task.borrow().keep_sorted;
+        if let Some(sort) = sort {
+            let selected = self.selected_task();
+            self.current_task
+                .borrow_mut()
+                .sort(sort, false, &self.window.config.priorities);
+            self.select_task(selected);
+        }
+    }
+
+    /// The currently selected task, if any.
+    fn selected_task(&self) -> Option<Rc<RefCell<ToDo>>> {
+        self.selection
+            .map(|index| Rc::clone(&self.current_task.borrow().sub_tasks[index]))
+    }
+
+    /// Move the selection to the given task, if it is a sub-task of the
+    /// current task.
+    fn select_task(&mut self, todo: Option<Rc<RefCell<ToDo>>>) {
+        if let Some(todo) = todo {
+            let current = self.current_task.borrow();
+            let index = current.sub_tasks.iter().position(|t| Rc::ptr_eq(t, &todo));
+            if index.is_some() {
+                self.selection = index;
+            }
+        }
     }
 }
 
@@ -715,3 +2083,58 @@ fn tab_num(line: &str) -> usize {
     }
     num / 4
 }
+
+/// Find the offset of the first visible row of a scrolling list, such that
+/// the selected row remains visible.
+fn scroll_offset(offset: usize, selection: Option<usize>, height: usize, len: usize) -> usize {
+    let mut offset = offset;
+    if let Some(index) = selection {
+        if index < offset {
+            offset = index;
+        } else if index >= offset + height {
+            offset = index + 1 - height;
+        }
+    }
+    // Don't leave empty rows at the bottom if rows are hidden above
+    if offset + height > len {
+        offset = len.saturating_sub(height);
+    }
+    offset
+}
+
+/// Split text into lines no wider than width, breaking at spaces where
+/// possible.
+fn wrap_lines(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    if width == 0 {
+        return lines;
+    }
+
+    let mut line = String::new();
+    let mut line_width = 0;
+    for word in text.split(' ') {
+        let word_width = UnicodeWidthStr::width(word);
+        if line_width > 0 && line_width + 1 + word_width > width {
+            lines.push(line);
+            line = String::new();
+            line_width = 0;
+        }
+        if line_width > 0 {
+            line.push(' ');
+            line_width += 1;
+        }
+        for ch in word.chars() {
+            let w = UnicodeWidthChar::width(ch).unwrap_or(0);
+            if line_width + w > width {
+                // Break words that are too long for a single line
+                lines.push(line);
+                line = String::new();
+                line_width = 0;
+            }
+            line.push(ch);
+            line_width += w;
+        }
+    }
+    lines.push(line);
+    lines
+}
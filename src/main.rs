@@ -10,18 +10,38 @@ fn main() {
     // Set up loggin to stderr
     setup_logger();
 
+    // Print a report instead, if asked
+    if env::args().nth(1).as_deref() == Some("report") {
+        let code = match yat::report::run(env::args().skip(2)) {
+            Ok(()) => 0,
+            Err(()) => 1,
+        };
+        process::exit(code);
+    }
+
+    // Or merge save files, e.g. as a git merge driver
+    if env::args().nth(1).as_deref() == Some("merge") {
+        let code = match yat::merge::run(env::args().skip(2)) {
+            Ok(0) => 0,
+            Ok(_) => 1,
+            Err(()) => 2,
+        };
+        process::exit(code);
+    }
+
     // Configuration
     let mut config = Config::default();
     let found_config = check_for_config();
-    match &found_config {
-        Some(configbuf) => {
-            config = configbuf.config(config);
-        }
-        None => (),
+    if let Some(configbuf) = &found_config {
+        config = configbuf.config(config);
     }
 
+    // Options can come anywhere after the program name
+    let readonly = env::args().any(|arg| arg == "--readonly");
+    let args = env::args().filter(|arg| arg != "--readonly");
+
     // Check for existence of valid save file
-    let view_result = match look_for_save(env::args()) {
+    let view_result = match look_for_save(args) {
         Ok(filename) => View::new_from_save(filename, config),
         Err(_) => View::new(config),
     };
@@ -30,6 +50,7 @@ fn main() {
     let mut view = view_result.unwrap_or_else(|_| {
         process::exit(1);
     });
+    view.set_readonly(readonly);
 
     // Run todo list manager
     view.run();
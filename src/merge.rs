@@ -0,0 +1,552 @@
+//! Three-way merging of save files, e.g. as a git merge driver.
+use crate::todo::{new_id, ToDo};
+use log::{info, warn};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::write;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+/// Usage of the merge command.
+const USAGE: &str = "Usage: yat merge BASE OURS THEIRS";
+
+/// Tag added to both versions of a task changed differently on each side.
+pub(crate) const CONFLICT_TAG: &str = "#conflict";
+
+/// Merge the save files named in the command line arguments, which follow
+/// `yat merge`, writing the result over OURS. Returns the number of
+/// conflicts.
+#[allow(clippy::result_unit_err)]
+pub fn run(args: impl Iterator<Item = String>) -> Result<usize, ()> {
+    let files: Vec<PathBuf> = args.map(PathBuf::from).collect();
+    let (base, ours, theirs) = match files.as_slice() {
+        [base, ours, theirs] => (base, ours, theirs),
+        _ => {
+            warn!("{}", USAGE);
+            return Err(());
+        }
+    };
+    let base = ToDo::read_tree(base)?;
+    let theirs = ToDo::read_tree(theirs)?;
+    let merged = ToDo::read_tree(ours)?;
+    let conflicts = merge_trees(&base, &merged, &theirs);
+
+    write(ours, merged.borrow().to_file_string())
+        .map_err(|err| warn!("Unable to write {}: {}", ours.display(), err))?;
+    match conflicts {
+        0 => info!("Merged without conflicts."),
+        n => warn!("Merged with {} conflicts, tagged {}.", n, CONFLICT_TAG),
+    }
+    Ok(conflicts)
+}
+
+/// Merge their changes to a todo list tree into ours, given the tree both
+/// started from. Returns the number of conflicts.
+pub(crate) fn merge_trees(
+    base: &Rc<RefCell<ToDo>>,
+    ours: &Rc<RefCell<ToDo>>,
+    theirs: &Rc<RefCell<ToDo>>,
+) -> usize {
+    align_moves(base, ours, theirs);
+    let ours_root = ours.borrow().clone();
+
+    // Settings for the whole list, then the tasks
+    let mut conflicts = 0;
+    {
+        let mut root = ours.borrow_mut();
+        let (b, t) = (base.borrow(), theirs.borrow());
+        if let Some(sort) = pick(Some(&b.keep_sorted), &ours_root.keep_sorted, &t.keep_sorted) {
+            root.keep_sorted = sort;
+        }
+        root.sub_tasks = merge_children(
+            &b.sub_tasks,
+            &ours_root.sub_tasks,
+            &t.sub_tasks,
+            &mut conflicts,
+        );
+    }
+    for sub_task in ours.borrow().sub_tasks.iter() {
+        sub_task.borrow_mut().parent = Rc::downgrade(ours);
+    }
+    ToDo::renew_duplicate_ids(ours, ours);
+    conflicts
+}
+
+/// Where a task with an ID sits in a tree: the ID of its parent (empty at
+/// the top level) and of the task before it, if any.
+struct Place {
+    parent: String,
+    after: Option<String>,
+}
+
+/// Find where each task with an ID sits in a tree, from the top down.
+/// Tasks below a task without an ID are left out, as they can't be placed.
+fn places(root: &Rc<RefCell<ToDo>>) -> Vec<(String, Place)> {
+    fn collect(todo: &Rc<RefCell<ToDo>>, parent: &str, places: &mut Vec<(String, Place)>) {
+        let mut after = None;
+        for sub_task in todo.borrow().sub_tasks.iter() {
+            let id = sub_task.borrow().id.clone();
+            if let Some(id) = &id {
+                let place = Place {
+                    parent: parent.to_string(),
+                    after: after.clone(),
+                };
+                places.push((id.clone(), place));
+                collect(sub_task, id, places);
+            }
+            after = id.or(after);
+        }
+    }
+    let mut places = Vec::new();
+    collect(root, "", &mut places);
+    places
+}
+
+/// Move the task with an ID in a tree under the task with another ID (or
+/// to the top level), following the task with the ID `after` if there is
+/// one. Nothing moves if either task is missing, or if the new parent is
+/// below the task.
+fn move_task(root: &Rc<RefCell<ToDo>>, id: &str, parent: &str, after: Option<&str>) {
+    let todo = match ToDo::find(root, id) {
+        Some(todo) if !Rc::ptr_eq(&todo, root) => todo,
+        _ => return,
+    };
+    let parent = match parent {
+        "" => Rc::clone(root),
+        id => match ToDo::find(root, id) {
+            Some(parent) => parent,
+            None => return,
+        },
+    };
+    let mut above = Some(Rc::clone(&parent));
+    while let Some(task) = above {
+        if Rc::ptr_eq(&task, &todo) {
+            return;
+        }
+        above = task.borrow().parent.upgrade();
+    }
+    if let (Some(old_parent), Some(index)) = (todo.borrow().parent.upgrade(), ToDo::index(&todo)) {
+        old_parent.borrow_mut().sub_tasks.remove(index);
+    }
+    let mut parent_ref = parent.borrow_mut();
+    let index = match after {
+        Some(after) => parent_ref
+            .sub_tasks
+            .iter()
+            .position(|t| t.borrow().id.as_deref() == Some(after))
+            .map_or(parent_ref.sub_tasks.len(), |index| index + 1),
+        None => 0,
+    };
+    parent_ref.sub_tasks.insert(index, Rc::clone(&todo));
+    todo.borrow_mut().parent = Rc::downgrade(&parent);
+}
+
+/// Move tasks found by ID on both sides under the same parent in all three
+/// trees, so that tasks moved on one side are merged with themselves and
+/// not as a deletion and an addition. A task moved differently on both
+/// sides stays where it is on our side.
+fn align_moves(base: &Rc<RefCell<ToDo>>, ours: &Rc<RefCell<ToDo>>, theirs: &Rc<RefCell<ToDo>>) {
+    let ours_places = places(ours);
+    let theirs_places = places(theirs);
+    let base_places = places(base);
+    let theirs_places: HashMap<&str, &Place> = theirs_places
+        .iter()
+        .map(|(id, place)| (id.as_str(), place))
+        .collect();
+    let base_places: HashMap<&str, &Place> = base_places
+        .iter()
+        .map(|(id, place)| (id.as_str(), place))
+        .collect();
+
+    for (id, o) in ours_places.iter() {
+        let t = match theirs_places.get(id.as_str()) {
+            Some(t) => t,
+            None => continue,
+        };
+        let b = base_places.get(id.as_str());
+        let parent = pick(b.map(|b| &b.parent), &o.parent, &t.parent).unwrap_or(o.parent.clone());
+        let after = match parent == o.parent {
+            true => o.after.as_deref(),
+            false => t.after.as_deref(),
+        };
+        if parent != o.parent {
+            move_task(ours, id, &parent, after);
+        }
+        if parent != t.parent {
+            move_task(theirs, id, &parent, after);
+        }
+        if b.is_some_and(|b| b.parent != parent) {
+            move_task(base, id, &parent, after);
+        }
+    }
+}
+
+/// Choose the value of a field from both sides of a merge: whichever side
+/// changed it from the base, or None if both changed it differently.
+fn pick<T: PartialEq + Clone>(base: Option<&T>, ours: &T, theirs: &T) -> Option<T> {
+    match base {
+        _ if ours == theirs => Some(ours.clone()),
+        Some(base) if base == ours => Some(theirs.clone()),
+        Some(base) if base == theirs => Some(ours.clone()),
+        _ => None,
+    }
+}
+
+/// Match tasks to other tasks at the same level, by ID or else by their
+/// text, returning the index of each task's match.
+fn match_tasks(from: &[Rc<RefCell<ToDo>>], to: &[Rc<RefCell<ToDo>>]) -> Vec<Option<usize>> {
+    let mut matches = vec![None; from.len()];
+    let mut taken = vec![false; to.len()];
+    let same_id = |a: &ToDo, b: &ToDo| a.id.is_some() && a.id == b.id;
+    let same_text = |a: &ToDo, b: &ToDo| a.task == b.task;
+    for same in [&same_id as &dyn Fn(&ToDo, &ToDo) -> bool, &same_text] {
+        for (i, todo) in from.iter().enumerate() {
+            if matches[i].is_some() {
+                continue;
+            }
+            let found = (0..to.len()).find(|&j| !taken[j] && same(&todo.borrow(), &to[j].borrow()));
+            if let Some(j) = found {
+                matches[i] = Some(j);
+                taken[j] = true;
+            }
+        }
+    }
+    matches
+}
+
+/// Find a task amongst tasks from another copy of the list, by ID or else
+/// by its text.
+pub(crate) fn find_task(todo: &Rc<RefCell<ToDo>>, todos: &[Rc<RefCell<ToDo>>]) -> Option<usize> {
+    match_tasks(std::slice::from_ref(todo), todos)[0]
+}
+
+/// Check if a task and its sub-tasks are the same in two trees, ignoring
+/// IDs given to tasks that were saved without one.
+fn same_subtree(a: &Rc<RefCell<ToDo>>, b: &Rc<RefCell<ToDo>>) -> bool {
+    let (a, b) = (a.borrow(), b.borrow());
+    let line = |todo: &ToDo| {
+        let mut todo = todo.clone();
+        todo.id = None;
+        todo.to_string()
+    };
+    line(&a) == line(&b)
+        && a.sub_tasks.len() == b.sub_tasks.len()
+        && a.sub_tasks
+            .iter()
+            .zip(b.sub_tasks.iter())
+            .all(|(a, b)| same_subtree(a, b))
+}
+
+/// Tag a task as conflicting.
+fn tag_conflict(todo: &Rc<RefCell<ToDo>>) {
+    let mut todo = todo.borrow_mut();
+    todo.task = format!("{} {}", todo.task, CONFLICT_TAG);
+}
+
+/// Merge the sub-tasks of a task. Tasks are kept in our order, with tasks
+/// added on their side following the task they followed there. A task
+/// deleted on one side is dropped, unless it was changed on the other.
+fn merge_children(
+    base: &[Rc<RefCell<ToDo>>],
+    ours: &[Rc<RefCell<ToDo>>],
+    theirs: &[Rc<RefCell<ToDo>>],
+    conflicts: &mut usize,
+) -> Vec<Rc<RefCell<ToDo>>> {
+    let theirs_for_ours = match_tasks(ours, theirs);
+    let base_for_ours = match_tasks(ours, base);
+    let base_for_theirs = match_tasks(theirs, base);
+    // Where each of their tasks ended up in the result
+    let mut placed: Vec<Option<Rc<RefCell<ToDo>>>> = vec![None; theirs.len()];
+    let mut merged = Vec::new();
+
+    for (i, todo) in ours.iter().enumerate() {
+        match (theirs_for_ours[i], base_for_ours[i]) {
+            (Some(j), k) => {
+                let base = k.map(|k| &base[k]);
+                let (todo, conflict) = merge_task(base, todo, &theirs[j], conflicts);
+                placed[j] = Some(Rc::clone(&todo));
+                merged.push(todo);
+                merged.extend(conflict);
+            }
+            // Deleted on their side
+            (None, Some(k)) if same_subtree(todo, &base[k]) => (),
+            (None, Some(_)) => {
+                tag_conflict(todo);
+                *conflicts += 1;
+                merged.push(Rc::clone(todo));
+            }
+            // Added on our side
+            (None, None) => merged.push(Rc::clone(todo)),
+        }
+    }
+
+    for (j, todo) in theirs.iter().enumerate() {
+        if theirs_for_ours.contains(&Some(j)) {
+            continue;
+        }
+        match base_for_theirs[j] {
+            // Deleted on our side
+            Some(k) if same_subtree(todo, &base[k]) => continue,
+            Some(_) => {
+                tag_conflict(todo);
+                *conflicts += 1;
+            }
+            // Added on their side
+            None => (),
+        }
+        let index = placed[..j]
+            .iter()
+            .rev()
+            .flatten()
+            .find_map(|previous| merged.iter().position(|t| Rc::ptr_eq(t, previous)))
+            .map_or(0, |index| index + 1);
+        merged.insert(index, Rc::clone(todo));
+        placed[j] = Some(Rc::clone(todo));
+    }
+    merged
+}
+
+/// Merge a task found on both sides, along with its sub-tasks. If both
+/// sides changed it differently, our version is kept and theirs is returned
+/// too, both tagged as conflicting.
+fn merge_task(
+    base: Option<&Rc<RefCell<ToDo>>>,
+    ours: &Rc<RefCell<ToDo>>,
+    theirs: &Rc<RefCell<ToDo>>,
+    conflicts: &mut usize,
+) -> (Rc<RefCell<ToDo>>, Option<Rc<RefCell<ToDo>>>) {
+    let (o, t) = (ours.borrow(), theirs.borrow());
+    let b = base.map(|base| base.borrow());
+    let b = b.as_deref();
+    let mut todo = o.clone();
+    let mut conflict = false;
+
+    // Take whichever side changed a field. Both changing one that matters
+    // is a conflict; otherwise ours is kept.
+    macro_rules! merge_field {
+        ($field:ident) => {
+            match pick(b.map(|b| &b.$field), &o.$field, &t.$field) {
+                Some(value) => todo.$field = value,
+                None => conflict = true,
+            }
+        };
+        (keep $field:ident) => {
+            if let Some(value) = pick(b.map(|b| &b.$field), &o.$field, &t.$field) {
+                todo.$field = value;
+            }
+        };
+    }
+    merge_field!(task);
+    merge_field!(complete);
+    merge_field!(priority);
+    merge_field!(recurrence);
+    merge_field!(due);
+    merge_field!(estimate);
+    merge_field!(keep folded);
+    merge_field!(keep created);
+    merge_field!(keep completed);
+    merge_field!(keep keep_sorted);
+    merge_field!(keep started);
+
+    // Time tracked on either side adds up
+    if let Some(b) = b {
+        todo.spent = o.spent + t.spent - b.spent;
+    }
+    for session in t.sessions.iter() {
+        if !todo.sessions.contains(session) {
+            todo.sessions.push(*session);
+        }
+    }
+    todo.sessions.sort();
+    // Dependencies added on either side are kept, unless removed on the other
+    let removed = |id: &String| b.is_some_and(|b| b.depends.contains(id));
+    todo.depends
+        .retain(|id| t.depends.contains(id) || !removed(id));
+    for id in t.depends.iter() {
+        if !todo.depends.contains(id) && !removed(id) {
+            todo.depends.push(id.clone());
+        }
+    }
+
+    let base_sub_tasks = b.map(|b| b.sub_tasks.clone()).unwrap_or_default();
+    todo.sub_tasks = merge_children(&base_sub_tasks, &o.sub_tasks, &t.sub_tasks, conflicts);
+    let todo = Rc::new(RefCell::new(todo));
+    for sub_task in todo.borrow().sub_tasks.iter() {
+        sub_task.borrow_mut().parent = Rc::downgrade(&todo);
+    }
+    if !conflict {
+        return (todo, None);
+    }
+
+    // Their version of the task alone, without its sub-tasks
+    *conflicts += 1;
+    tag_conflict(&todo);
+    let mut other = t.clone();
+    other.id = Some(new_id());
+    other.sub_tasks = Vec::new();
+    let other = Rc::new(RefCell::new(other));
+    tag_conflict(&other);
+    (todo, Some(other))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    /// Merge three save files, returning the result and the number of
+    /// conflicts.
+    fn merge(base: &str, ours: &str, theirs: &str) -> (Rc<RefCell<ToDo>>, usize) {
+        let base = ToDo::tree_from_string(base).unwrap();
+        let ours = ToDo::tree_from_string(ours).unwrap();
+        let theirs = ToDo::tree_from_string(theirs).unwrap();
+        let conflicts = merge_trees(&base, &ours, &theirs);
+        (ours, conflicts)
+    }
+
+    /// Lines of a merged tree, without the conflict copies' random IDs.
+    fn lines(root: &Rc<RefCell<ToDo>>) -> Vec<String> {
+        root.borrow()
+            .to_file_string()
+            .lines()
+            .map(|line| match line.find(" id:") {
+                Some(index) if line.contains(CONFLICT_TAG) => line[..index].to_string(),
+                _ => line.to_string(),
+            })
+            .collect()
+    }
+
+    /// The task with an ID in a merged tree.
+    fn task(root: &Rc<RefCell<ToDo>>, id: &str) -> ToDo {
+        ToDo::find(root, id).unwrap().borrow().clone()
+    }
+
+    #[test]
+    fn adds_on_both_sides() {
+        let (merged, conflicts) = merge(
+            "[ ] ( ) a id:aaaaaa\n",
+            "[ ] ( ) a id:aaaaaa\n[ ] ( ) b id:bbbbbb\n",
+            "[ ] ( ) c id:cccccc\n[ ] ( ) a id:aaaaaa\n",
+        );
+        assert_eq!(conflicts, 0);
+        assert_eq!(
+            lines(&merged),
+            [
+                "[ ] ( ) c id:cccccc",
+                "[ ] ( ) a id:aaaaaa",
+                "[ ] ( ) b id:bbbbbb",
+            ]
+        );
+    }
+
+    #[test]
+    fn delete_of_unchanged_task() {
+        let (merged, conflicts) = merge(
+            "[ ] ( ) a id:aaaaaa\n[ ] ( ) b id:bbbbbb\n",
+            "[ ] ( ) a id:aaaaaa\n",
+            "[X] ( ) a id:aaaaaa\n[ ] ( ) b id:bbbbbb\n",
+        );
+        assert_eq!(conflicts, 0);
+        assert_eq!(lines(&merged), ["[X] ( ) a id:aaaaaa"]);
+    }
+
+    #[test]
+    fn delete_of_changed_task_conflicts() {
+        let (merged, conflicts) = merge(
+            "[ ] ( ) a id:aaaaaa\n[ ] ( ) b id:bbbbbb\n",
+            "[ ] ( ) a id:aaaaaa\n",
+            "[ ] ( ) a id:aaaaaa\n[ ] ( ) b edited id:bbbbbb\n",
+        );
+        assert_eq!(conflicts, 1);
+        assert_eq!(
+            lines(&merged),
+            ["[ ] ( ) a id:aaaaaa", "[ ] ( ) b edited #conflict"]
+        );
+    }
+
+    #[test]
+    fn field_changed_on_both_sides_conflicts() {
+        let (merged, conflicts) = merge(
+            "[ ] ( ) a id:aaaaaa\n",
+            "[ ] (A) a id:aaaaaa\n",
+            "[ ] (C) a id:aaaaaa\n",
+        );
+        assert_eq!(conflicts, 1);
+        assert_eq!(
+            lines(&merged),
+            ["[ ] (A) a #conflict", "[ ] (C) a #conflict"]
+        );
+    }
+
+    #[test]
+    fn fields_changed_on_different_sides_merge() {
+        let (merged, conflicts) = merge(
+            "[ ] ( ) a id:aaaaaa\n",
+            "[X] ( ) a id:aaaaaa\n",
+            "[ ] (B) a due:2020-01-31 id:aaaaaa\n",
+        );
+        assert_eq!(conflicts, 0);
+        assert_eq!(lines(&merged), ["[X] (B) a due:2020-01-31 id:aaaaaa"]);
+    }
+
+    #[test]
+    fn spent_time_adds_up() {
+        let (merged, conflicts) = merge(
+            "[ ] ( ) a id:aaaaaa spent:1h\n",
+            "[ ] ( ) a id:aaaaaa spent:2h\n",
+            "[ ] ( ) a id:aaaaaa spent:1h30m\n",
+        );
+        assert_eq!(conflicts, 0);
+        assert_eq!(task(&merged, "aaaaaa").spent, Duration::minutes(150));
+    }
+
+    #[test]
+    fn depends_added_and_removed() {
+        let (merged, conflicts) = merge(
+            "[ ] ( ) a id:aaaaaa depends:xxxxxx,yyyyyy\n",
+            "[ ] ( ) a id:aaaaaa depends:xxxxxx,yyyyyy,zzzzzz\n",
+            "[ ] ( ) a id:aaaaaa depends:yyyyyy,wwwwww\n",
+        );
+        assert_eq!(conflicts, 0);
+        assert_eq!(
+            task(&merged, "aaaaaa").depends,
+            ["yyyyyy", "zzzzzz", "wwwwww"]
+        );
+    }
+
+    #[test]
+    fn task_moved_on_one_side_keeps_edits_from_the_other() {
+        let (merged, conflicts) = merge(
+            "[ ] ( ) p id:pppppp\n    [ ] ( ) x id:xxxxxx\n[ ] ( ) q id:qqqqqq\n",
+            "[ ] ( ) p id:pppppp\n    [ ] ( ) x edited id:xxxxxx\n[ ] ( ) q id:qqqqqq\n",
+            "[ ] ( ) p id:pppppp\n[ ] ( ) q id:qqqqqq\n    [ ] ( ) x id:xxxxxx\n",
+        );
+        assert_eq!(conflicts, 0);
+        assert_eq!(
+            lines(&merged),
+            [
+                "[ ] ( ) p id:pppppp",
+                "[ ] ( ) q id:qqqqqq",
+                "    [ ] ( ) x edited id:xxxxxx",
+            ]
+        );
+    }
+
+    #[test]
+    fn task_moved_on_both_sides_stays_where_ours_is() {
+        let (merged, conflicts) = merge(
+            "[ ] ( ) p id:pppppp\n    [ ] ( ) x id:xxxxxx\n[ ] ( ) q id:qqqqqq\n",
+            "[ ] ( ) p id:pppppp\n[ ] ( ) x id:xxxxxx\n[ ] ( ) q id:qqqqqq\n",
+            "[ ] ( ) p id:pppppp\n[ ] ( ) q id:qqqqqq\n    [ ] ( ) x id:xxxxxx\n",
+        );
+        assert_eq!(conflicts, 0);
+        assert_eq!(
+            lines(&merged),
+            [
+                "[ ] ( ) p id:pppppp",
+                "[ ] ( ) x id:xxxxxx",
+                "[ ] ( ) q id:qqqqqq",
+            ]
+        );
+    }
+}
@@ -0,0 +1,341 @@
+//! Outline view of the whole todo list tree, with folding.
+use crate::todo::ToDo;
+use crate::{scroll_offset, View};
+use std::cell::RefCell;
+use std::rc::Rc;
+use termion::event::Key;
+use unicode_width::UnicodeWidthStr;
+
+/// State of the outline view.
+pub struct Outline {
+    /// Task under the cursor.
+    pub(crate) cursor: Option<Rc<RefCell<ToDo>>>,
+    /// Index of the first visible row.
+    offset: usize,
+}
+
+/// A visible row of the outline.
+struct Row {
+    todo: Rc<RefCell<ToDo>>,
+    /// Indentation guides drawn before the task.
+    guide: String,
+}
+
+/// Flatten the unfolded part of the tree below todo into rows, leaving
+/// out tasks that aren't shown.
+fn collect_rows(
+    todo: &Rc<RefCell<ToDo>>,
+    prefix: &str,
+    top: bool,
+    shown: &dyn Fn(&ToDo) -> bool,
+    rows: &mut Vec<Row>,
+) {
+    let sub_tasks: Vec<_> = todo
+        .borrow()
+        .sub_tasks
+        .iter()
+        .filter(|sub_task| shown(&sub_task.borrow()))
+        .cloned()
+        .collect();
+    for (i, sub_task) in sub_tasks.iter().enumerate() {
+        let last = i + 1 == sub_tasks.len();
+        let (branch, continuation) = match (top, last) {
+            (true, _) => ("", ""),
+            (false, false) => ("├─", "│ "),
+            (false, true) => ("└─", "  "),
+        };
+        let sub_task_ref = sub_task.borrow();
+        let marker = match (sub_task_ref.sub_tasks.is_empty(), sub_task_ref.folded) {
+            (true, _) => " ",
+            (false, false) => "▾",
+            (false, true) => "▸",
+        };
+        rows.push(Row {
+            todo: Rc::clone(sub_task),
+            guide: format!("{}{}{}", prefix, branch, marker),
+        });
+        if !sub_task_ref.folded {
+            let prefix = format!("{}{}", prefix, continuation);
+            collect_rows(sub_task, &prefix, false, shown, rows);
+        }
+    }
+}
+
+impl<'a> View<'a> {
+    /// Enter or leave the outline view. The outline is always shown from
+    /// the root of the tree, so focus first returns to the root.
+    pub(crate) fn toggle_outline(&mut self) {
+        self.marked.clear();
+        match self.outline.take() {
+            Some(outline) => {
+                // Select the top-level task containing the cursor
+                let mut top = outline.cursor;
+                while let Some(todo) = top.clone() {
+                    let parent = todo.borrow().parent.upgrade();
+                    match parent {
+                        Some(parent) if parent.borrow().parent.upgrade().is_some() => {
+                            top = Some(parent)
+                        }
+                        _ => break,
+                    }
+                }
+                if let Some(todo) = top {
+                    self.selection = ToDo::index(&todo);
+                }
+            }
+            None => {
+                let cursor = match self.selection {
+                    Some(index) => Some(Rc::clone(&self.current_task.borrow().sub_tasks[index])),
+                    None => self.root_task().borrow().sub_tasks.first().cloned(),
+                };
+                self.back(self.stack.len());
+                self.outline = Some(Outline { cursor, offset: 0 });
+            }
+        }
+    }
+
+    /// Find the rows of the outline and the index of the cursor's row.
+    fn outline_rows(&self) -> (Vec<Row>, Option<usize>) {
+        let mut rows = Vec::new();
+        let shown = |todo: &ToDo| self.is_shown(todo);
+        collect_rows(&self.root_task(), "", true, &shown, &mut rows);
+        let cursor = self.outline.as_ref().and_then(|o| o.cursor.as_ref());
+        let index = cursor.and_then(|todo| rows.iter().position(|row| Rc::ptr_eq(&row.todo, todo)));
+        (rows, index)
+    }
+
+    /// Display the outline view.
+    pub(crate) fn draw_outline(&mut self) {
+        self.window.clear();
+        self.window.hide_cursor();
+
+        let panels = self.panels();
+        let (rows, index) = self.outline_rows();
+        let cursor = index.map(|i| Rc::clone(&rows[i].todo));
+
+        // Parent panel shows the path to the cursor, shortened to fit
+        if let Some(todo) = &cursor {
+            let inner = panels.parent.inner();
+            let path: String = Self::make_breadcrumb(todo, inner.width.saturating_sub(3))
+                .into_iter()
+                .map(|(piece, _)| piece)
+                .collect();
+            self.window.wrap_print(inner.y, inner.x, inner.width, &path);
+        }
+        self.window.panel(&panels.parent, &self.parent_title());
+
+        // Selection panel, or a status message
+        self.window.panel(&panels.selection, "Selection");
+        match (self.status.clone(), &cursor) {
+            (Some(status), _) => self.draw_status(&panels.selection, &status),
+            (None, Some(todo)) => {
+                let inner = panels.selection.inner();
+                self.window.colour_on(6, 8);
+                self.window.wrap_print(
+                    inner.y,
+                    inner.x + 1,
+                    inner.width.saturating_sub(1),
+                    &todo.borrow().task,
+                );
+                self.window.colour_off();
+            }
+            (None, None) => (),
+        }
+
+        // Outline panel
+        let rect = panels.main;
+        let inner = rect.inner();
+        self.window.panel(&rect, "Outline");
+        let offset = match self.outline.as_mut() {
+            Some(outline) => {
+                outline.offset = scroll_offset(outline.offset, index, inner.height, rows.len());
+                outline.offset
+            }
+            None => 0,
+        };
+        let end = rows.len().min(offset + inner.height);
+        for (y, row) in (inner.y..).zip(rows[offset..end].iter()) {
+            let x = inner.x + 2;
+            self.window.colour_on(0, 8);
+            self.window.mvprintw(y, x, &row.guide);
+            self.window.colour_off();
+            let x = x + UnicodeWidthStr::width(row.guide.as_str()) + 1;
+            let width = (inner.x + inner.width).saturating_sub(x + 1);
+            self.print_task(y, x, width, &row.todo.borrow());
+        }
+        self.more_indicators(&rect, offset, rows.len() - end);
+
+        if let Some(i) = index {
+            self.window.colour_on(6, 8);
+            self.window.mvprintw(inner.y + i - offset, inner.x, ">");
+            self.window.colour_off();
+        }
+        self.window.refresh();
+    }
+
+    /// Respond to a key press in the outline view.
+    pub(crate) fn outline_key(&mut self, key: Key) {
+        let config = &self.window.config;
+        match key {
+            key if key == config.quit => self.quit = true,
+            key if key == config.outline || key == config.back => self.toggle_outline(),
+            key if key == config.save => {
+                let _ = self.save();
+            }
+            key if key == config.up => self.outline_move(-1),
+            key if key == config.down => self.outline_move(1),
+            key if key == config.page_up => {
+                let height = self.panels().main.inner().height as isize;
+                self.outline_move(-height)
+            }
+            key if key == config.page_down => {
+                let height = self.panels().main.inner().height as isize;
+                self.outline_move(height)
+            }
+            key if key == config.top => self.outline_move(isize::MIN),
+            key if key == config.bottom => self.outline_move(isize::MAX),
+            key if key == config.fold || key == config.focus => {
+                if let Some(todo) = self.outline.as_ref().and_then(|o| o.cursor.clone()) {
+                    let mut todo = todo.borrow_mut();
+                    if !todo.sub_tasks.is_empty() {
+                        todo.folded = !todo.folded;
+                    }
+                }
+            }
+            key if key == config.indent => {
+                if let Some(todo) = self.outline.as_ref().and_then(|o| o.cursor.clone()) {
+                    let parent = todo.borrow().parent.upgrade();
+                    if let (Some(parent), Some(1..)) = (parent, ToDo::index(&todo)) {
+                        self.checkpoint(parent);
+                    }
+                    ToDo::indent(&todo);
+                }
+            }
+            key if key == config.outdent => {
+                if let Some(todo) = self.outline.as_ref().and_then(|o| o.cursor.clone()) {
+                    let parent = todo.borrow().parent.upgrade();
+                    if let Some(grandparent) = parent.and_then(|p| p.borrow().parent.upgrade()) {
+                        self.checkpoint(grandparent);
+                    }
+                    ToDo::outdent(&todo);
+                }
+            }
+            key if key == config.add => self.outline_apply(View::add_task_from_input),
+            key if key == config.edit => self.outline_apply(View::edit_task),
+            key if key == config.delete => self.outline_apply(View::remove_task),
+            key if key == config.complete => self.outline_apply(View::complete_task),
+            key if key == config.increase => self.outline_apply(View::increase_priority),
+            key if key == config.decrease => self.outline_apply(View::decrease_priority),
+            key if key == config.task_up => self.outline_apply(|view| view.move_task(true)),
+            key if key == config.task_down => self.outline_apply(|view| view.move_task(false)),
+            key if key == config.cut => self.outline_apply(View::cut_task),
+            key if key == config.paste => self.outline_apply(View::paste_task),
+            key if key == config.yank => self.outline_apply(View::yank_task),
+            key if key == config.duplicate => self.outline_apply(View::duplicate_task),
+            key if key == config.copy_text => self.outline_apply(View::copy_text),
+            key if key == config.tag => self.outline_apply(View::tag_tasks),
+            key if key == config.repeat => self.outline_apply(View::set_recurrence),
+            key if key == config.timer => self.outline_apply(View::toggle_timer),
+            key if key == config.estimate => self.outline_apply(View::set_estimate),
+            key if key == config.filter => {
+                self.set_estimate_filter();
+                if self.outline_rows().1.is_none() {
+                    self.outline_move(0);
+                }
+            }
+            key if key == config.link => self.outline_apply(View::link_task),
+            key if key == config.jump => self.jump_prompt(),
+            key if key == config.actionable => {
+                self.toggle_actionable();
+                if self.outline_rows().1.is_none() {
+                    self.outline_move(0);
+                }
+            }
+            key if key == config.hide => {
+                self.toggle_hide_complete();
+                if self.outline_rows().1.is_none() {
+                    self.outline_move(0);
+                }
+            }
+            key if key == config.undo => {
+                self.undo();
+                if self.outline_rows().1.is_none() {
+                    self.outline_move(0);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Move the outline cursor to a task, unfolding the tasks above it so
+    /// that it can be seen.
+    pub(crate) fn outline_reveal(&mut self, todo: &Rc<RefCell<ToDo>>) {
+        let mut parent = todo.borrow().parent.upgrade();
+        while let Some(task) = parent {
+            task.borrow_mut().folded = false;
+            parent = task.borrow().parent.upgrade();
+        }
+        if let Some(outline) = self.outline.as_mut() {
+            outline.cursor = Some(Rc::clone(todo));
+        }
+    }
+
+    /// Move the outline cursor by a number of rows, without wrapping.
+    fn outline_move(&mut self, step: isize) {
+        let (rows, index) = self.outline_rows();
+        if rows.is_empty() {
+            return;
+        }
+        let new_index = match index {
+            Some(i) => (i as isize)
+                .saturating_add(step)
+                .clamp(0, rows.len() as isize - 1),
+            None => 0,
+        };
+        if let Some(outline) = self.outline.as_mut() {
+            outline.cursor = Some(Rc::clone(&rows[new_index as usize].todo));
+        }
+    }
+
+    /// Apply an action from the main view to the task under the outline
+    /// cursor, by temporarily focusing on the cursor's parent.
+    fn outline_apply(&mut self, action: fn(&mut View<'a>)) {
+        let cursor = self.outline.as_ref().and_then(|o| o.cursor.clone());
+        let root = Rc::clone(&self.current_task);
+        let (parent, index) = match &cursor {
+            Some(todo) => {
+                let parent = todo.borrow().parent.upgrade();
+                (
+                    parent.unwrap_or_else(|| Rc::clone(&root)),
+                    ToDo::index(todo),
+                )
+            }
+            None => (Rc::clone(&root), None),
+        };
+
+        let previous_selection = self.selection;
+        self.current_task = Rc::clone(&parent);
+        self.selection = index;
+        action(self);
+        self.keep_sorted();
+
+        // Keep the cursor on the same task, or a neighbour if it was removed
+        let new_cursor = {
+            let sub_tasks = &parent.borrow().sub_tasks;
+            match (self.selection, index) {
+                (Some(i), _) => sub_tasks.get(i).cloned(),
+                (None, Some(i)) if !sub_tasks.is_empty() => {
+                    sub_tasks.get(i.min(sub_tasks.len() - 1)).cloned()
+                }
+                _ if !Rc::ptr_eq(&parent, &root) => Some(Rc::clone(&parent)),
+                _ => None,
+            }
+        };
+
+        self.current_task = root;
+        self.selection = previous_selection;
+        if let Some(outline) = self.outline.as_mut() {
+            outline.cursor = new_cursor;
+        }
+    }
+}
@@ -0,0 +1,114 @@
+//! Reports summarising a save file, printed from the command line.
+use crate::todo::{short_duration, ToDo, DATE_FORMAT, DAY_FORMAT};
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use dirs::home_dir;
+use log::warn;
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+/// Usage of the report command.
+const USAGE: &str = "Usage: yat report time [--since YYYY-MM-DD] [--task ID] [FILE]";
+
+/// Run the report named in the command line arguments, which follow
+/// `yat report`.
+#[allow(clippy::result_unit_err)]
+pub fn run(args: impl Iterator<Item = String>) -> Result<(), ()> {
+    let mut args = args;
+    match args.next().as_deref() {
+        Some("time") => (),
+        _ => {
+            warn!("{}", USAGE);
+            return Err(());
+        }
+    }
+
+    let mut since = None;
+    let mut task = None;
+    let mut filename = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--since" => match args.next().as_deref().and_then(parse_since) {
+                Some(date) => since = Some(date),
+                None => {
+                    warn!("--since needs a date, e.g. 2020-01-31. {}", USAGE);
+                    return Err(());
+                }
+            },
+            "--task" => match args.next() {
+                Some(id) => task = Some(id),
+                None => {
+                    warn!("--task needs the ID of a task. {}", USAGE);
+                    return Err(());
+                }
+            },
+            _ if filename.is_none() => filename = Some(PathBuf::from(arg)),
+            _ => {
+                warn!("{}", USAGE);
+                return Err(());
+            }
+        }
+    }
+    let filename = match filename {
+        Some(filename) => filename,
+        None => home_dir().ok_or(())?.join(".todo").join("save.txt"),
+    };
+
+    let root = ToDo::read_tree(&filename)?;
+    let mut lines = Vec::new();
+    // Either a single task and the tasks below it, or the whole list
+    let root = match task {
+        Some(id) => {
+            let todo = ToDo::find(&root, &id).ok_or_else(|| warn!("No task with ID {}.", id))?;
+            time_lines(&todo, since, 0, &mut lines);
+            todo
+        }
+        None => {
+            for sub_task in root.borrow().sub_tasks.iter() {
+                time_lines(sub_task, since, 0, &mut lines);
+            }
+            root
+        }
+    };
+    match since {
+        Some(since) => println!("Time tracked since {}:", since.format(DATE_FORMAT)),
+        None => println!("Time tracked:"),
+    }
+    for (time, depth, task) in lines.iter() {
+        println!("{:>9}  {}{}", time, "    ".repeat(*depth), task);
+    }
+    let total = root.borrow().time_spent(since);
+    println!("{:>9}  Total", short_duration(total));
+    Ok(())
+}
+
+/// Parse the start of the period to report on, either a date or a date
+/// and time.
+fn parse_since(text: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(text, DATE_FORMAT)
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(text, DAY_FORMAT)
+                .ok()?
+                .and_hms_opt(0, 0, 0)
+        })
+}
+
+/// List the time tracked against a task and the tasks below it, leaving
+/// out tasks without any.
+fn time_lines(
+    todo: &Rc<RefCell<ToDo>>,
+    since: Option<NaiveDateTime>,
+    depth: usize,
+    lines: &mut Vec<(String, usize, String)>,
+) {
+    let todo = todo.borrow();
+    let time = todo.time_spent(since);
+    if time <= Duration::zero() {
+        return;
+    }
+    lines.push((short_duration(time), depth, todo.task.clone()));
+    for sub_task in todo.sub_tasks.iter() {
+        time_lines(sub_task, since, depth + 1, lines);
+    }
+}
@@ -1,18 +1,468 @@
 /// Functionality for storing todo lists in a tree data structure.
+use crate::config::Priorities;
+use crate::tab_num;
+use chrono::{Datelike, Duration, Local, NaiveDate, NaiveDateTime, Weekday};
 use log::{info, warn};
 use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::hash_map::RandomState;
+use std::collections::HashSet;
 use std::fmt;
-use std::fs::File;
-use std::io::Write;
+use std::fs::{self, File};
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, Write};
 use std::path::Path;
 use std::rc::{Rc, Weak};
 
-/// Task priority.
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
-pub enum Priority {
-    Low,
-    Medium,
-    High,
+/// Keys of the `key:value` metadata that may follow a task in the save
+/// file.
+const METADATA_KEYS: [&str; 12] = [
+    "folded",
+    "created",
+    "completed",
+    "sort",
+    "repeat",
+    "due",
+    "spent",
+    "session",
+    "started",
+    "estimate",
+    "id",
+    "depends",
+];
+
+/// Format of creation times in the save file.
+pub const DATE_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+/// Number of characters in a task ID.
+const ID_LENGTH: usize = 6;
+
+/// Characters used in task IDs.
+const ID_CHARS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+/// Format of due dates in the save file.
+pub const DAY_FORMAT: &str = "%Y-%m-%d";
+
+/// Property that tasks can be sorted by.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortKey {
+    /// Highest priority first.
+    Priority,
+    /// Tasks not yet completed first.
+    Completion,
+    /// Alphabetical order of the task, ignoring case.
+    Alphabetical,
+    /// Oldest first.
+    Created,
+    /// Least estimated effort first.
+    Estimate,
+}
+
+/// Way of sorting a list of tasks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sort {
+    pub key: SortKey,
+    pub reverse: bool,
+}
+
+impl Sort {
+    /// Compare two tasks. Equal tasks keep their order when sorting.
+    fn compare(&self, a: &ToDo, b: &ToDo, priorities: &Priorities) -> Ordering {
+        let ordering = match self.key {
+            SortKey::Priority => priorities
+                .rank(a.priority)
+                .cmp(&priorities.rank(b.priority)),
+            SortKey::Completion => a.complete.cmp(&b.complete),
+            SortKey::Alphabetical => a.task.to_lowercase().cmp(&b.task.to_lowercase()),
+            SortKey::Created => a.created.cmp(&b.created),
+            SortKey::Estimate => a.effort().0.cmp(&b.effort().0),
+        };
+        if self.reverse {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+
+    /// Parse from the save file format, e.g. `priority` or `-priority`
+    /// when reversed.
+    fn from_name(name: &str) -> Option<Sort> {
+        let (reverse, name) = match name.strip_prefix('-') {
+            Some(name) => (true, name),
+            None => (false, name),
+        };
+        let key = match name {
+            "priority" => SortKey::Priority,
+            "completion" => SortKey::Completion,
+            "alphabetical" => SortKey::Alphabetical,
+            "created" => SortKey::Created,
+            "estimate" => SortKey::Estimate,
+            _ => return None,
+        };
+        Some(Sort { key, reverse })
+    }
+}
+
+impl fmt::Display for Sort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.reverse {
+            write!(f, "-")?;
+        }
+        match self.key {
+            SortKey::Priority => write!(f, "priority"),
+            SortKey::Completion => write!(f, "completion"),
+            SortKey::Alphabetical => write!(f, "alphabetical"),
+            SortKey::Created => write!(f, "created"),
+            SortKey::Estimate => write!(f, "estimate"),
+        }
+    }
+}
+
+/// Rule for when a recurring task comes round again.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Recurrence {
+    /// Every day.
+    Daily,
+    /// Every week, on the given days or a week after the last occurrence.
+    Weekly(Vec<Weekday>),
+    /// On the same day every month.
+    Monthly,
+    /// A number of days after the task was completed.
+    After(u32),
+}
+
+impl Recurrence {
+    /// Parse from the save file format, e.g. `weekly-mon-thu` or `after-3`.
+    /// Spaces may be used instead of dashes.
+    pub fn from_name(name: &str) -> Option<Recurrence> {
+        let mut words = name.split(['-', ' ']).filter(|word| !word.is_empty());
+        let recurrence = match words.next()?.to_lowercase().as_str() {
+            "daily" => Recurrence::Daily,
+            "weekly" => {
+                let days = words.by_ref().map(|day| day.parse().ok());
+                Recurrence::Weekly(days.collect::<Option<_>>()?)
+            }
+            "monthly" => Recurrence::Monthly,
+            "after" => match words.next()?.parse() {
+                Ok(days) if days > 0 => Recurrence::After(days),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        match words.next() {
+            Some(_) => None,
+            None => Some(recurrence),
+        }
+    }
+
+    /// Date of the next occurrence, following on from the date the last
+    /// one was due (if any) and the date it was completed.
+    pub fn next(&self, due: Option<NaiveDate>, completed: NaiveDate) -> NaiveDate {
+        if let Recurrence::After(days) = self {
+            return completed + Duration::days(i64::from(*days));
+        }
+        let start = due.unwrap_or(completed);
+        let mut next = start;
+        // Move on from the occurrence just done, skipping any that were
+        // missed while the task was overdue
+        while next <= completed || next == start {
+            next = match self {
+                Recurrence::Daily => next + Duration::days(1),
+                Recurrence::Weekly(days) if days.is_empty() => next + Duration::days(7),
+                Recurrence::Weekly(days) => (1..=7)
+                    .map(|n| next + Duration::days(n))
+                    .find(|date| days.contains(&date.weekday()))
+                    .unwrap_or(next + Duration::days(7)),
+                Recurrence::Monthly => add_month(next, start.day()),
+                Recurrence::After(_) => unreachable!(),
+            };
+        }
+        next
+    }
+}
+
+impl fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Recurrence::Daily => write!(f, "daily"),
+            Recurrence::Weekly(days) => {
+                write!(f, "weekly")?;
+                for day in days {
+                    write!(f, "-{}", day.to_string().to_lowercase())?;
+                }
+                Ok(())
+            }
+            Recurrence::Monthly => write!(f, "monthly"),
+            Recurrence::After(days) => write!(f, "after-{}", days),
+        }
+    }
+}
+
+/// The date a month later, on the given day or the last day of the month
+/// if it is shorter.
+fn add_month(date: NaiveDate, day: u32) -> NaiveDate {
+    let (year, month) = match date.month() {
+        12 => (date.year() + 1, 1),
+        month => (date.year(), month + 1),
+    };
+    (1..=day)
+        .rev()
+        .find_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+        .unwrap_or(date)
+}
+
+/// Format a duration for the save file, e.g. `1h30m15s`.
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.num_seconds().max(0);
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    let mut text = String::new();
+    if hours > 0 {
+        text.push_str(&format!("{}h", hours));
+    }
+    if minutes > 0 {
+        text.push_str(&format!("{}m", minutes));
+    }
+    if seconds > 0 || text.is_empty() {
+        text.push_str(&format!("{}s", seconds));
+    }
+    text
+}
+
+/// Format a duration to the minute for display, e.g. `1h30m`.
+pub fn short_duration(duration: Duration) -> String {
+    let minutes = duration.num_minutes().max(0);
+    match minutes / 60 {
+        0 => format!("{}m", minutes),
+        hours => format!("{}h{:02}m", hours, minutes % 60),
+    }
+}
+
+/// Parse a duration such as `1h30m`, `45m` or `20s`.
+pub fn parse_duration(text: &str) -> Option<Duration> {
+    let mut total = Duration::zero();
+    let mut number = String::new();
+    for ch in text.chars() {
+        if ch.is_ascii_digit() {
+            number.push(ch);
+            continue;
+        }
+        let n: i64 = number.parse().ok()?;
+        number.clear();
+        total += match ch {
+            'h' => Duration::hours(n),
+            'm' => Duration::minutes(n),
+            's' => Duration::seconds(n),
+            _ => return None,
+        };
+    }
+    match number.is_empty() && !text.is_empty() {
+        true => Some(total),
+        false => None,
+    }
+}
+
+/// Estimated effort of a task, as a length of time or in story points.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Estimate {
+    Time(Duration),
+    Points(u32),
+}
+
+impl Estimate {
+    /// Parse from the save file format, e.g. `2h`, `1h30m` or `3pt`.
+    pub fn from_name(name: &str) -> Option<Estimate> {
+        match name.strip_suffix("pt") {
+            Some(points) => points
+                .parse()
+                .ok()
+                .filter(|&points| points > 0)
+                .map(Estimate::Points),
+            None => parse_duration(name)
+                .filter(|duration| *duration > Duration::zero())
+                .map(Estimate::Time),
+        }
+    }
+}
+
+impl fmt::Display for Estimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Estimate::Time(duration) => write!(f, "{}", format_duration(*duration)),
+            Estimate::Points(points) => write!(f, "{}pt", points),
+        }
+    }
+}
+
+/// Estimated effort of a number of tasks added together, keeping time and
+/// story points apart. Ordered by time, then points.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Effort {
+    pub time: Duration,
+    pub points: u32,
+}
+
+impl Effort {
+    /// No effort at all.
+    pub fn zero() -> Effort {
+        Effort {
+            time: Duration::zero(),
+            points: 0,
+        }
+    }
+
+    /// Whether no effort has been estimated.
+    pub fn is_zero(&self) -> bool {
+        *self == Effort::zero()
+    }
+
+    /// Add an estimate for one more task.
+    fn add(&mut self, estimate: Estimate) {
+        match estimate {
+            Estimate::Time(duration) => self.time += duration,
+            Estimate::Points(points) => self.points += points,
+        }
+    }
+}
+
+impl std::ops::AddAssign for Effort {
+    fn add_assign(&mut self, other: Effort) {
+        self.time += other.time;
+        self.points += other.points;
+    }
+}
+
+impl fmt::Display for Effort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if self.time > Duration::zero() {
+            parts.push(format_duration(self.time));
+        }
+        if self.points > 0 || parts.is_empty() {
+            parts.push(format!("{}pt", self.points));
+        }
+        write!(f, "{}", parts.join(" "))
+    }
+}
+
+/// Ways of comparing estimates in a filter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    AtMost,
+    AtLeast,
+    Less,
+    Greater,
+    Equal,
+}
+
+/// Comparisons in the order they are tried, longest first so that they can
+/// be matched as prefixes.
+const COMPARISONS: [Comparison; 5] = [
+    Comparison::AtMost,
+    Comparison::AtLeast,
+    Comparison::Less,
+    Comparison::Greater,
+    Comparison::Equal,
+];
+
+impl Comparison {
+    /// Symbol of the comparison in a filter.
+    fn symbol(self) -> &'static str {
+        match self {
+            Comparison::AtMost => "<=",
+            Comparison::AtLeast => ">=",
+            Comparison::Less => "<",
+            Comparison::Greater => ">",
+            Comparison::Equal => "=",
+        }
+    }
+
+    /// Whether the comparison holds for how one value orders against
+    /// another.
+    fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            Comparison::AtMost => ordering != Ordering::Greater,
+            Comparison::AtLeast => ordering != Ordering::Less,
+            Comparison::Less => ordering == Ordering::Less,
+            Comparison::Greater => ordering == Ordering::Greater,
+            Comparison::Equal => ordering == Ordering::Equal,
+        }
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
+
+/// Condition on the estimated effort of tasks, used to filter them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EstimateFilter {
+    /// Tasks without any estimate, including the tasks below them.
+    Unestimated,
+    /// Tasks whose total effort compares with an estimate, e.g. `<=2h`.
+    /// Tasks without an estimate in the same unit never match.
+    Compare(Comparison, Estimate),
+}
+
+impl EstimateFilter {
+    /// Parse a filter, e.g. `<=2h`, `>3pt`, `1h` or `none`.
+    pub fn from_name(name: &str) -> Option<EstimateFilter> {
+        let name: String = name.split_whitespace().collect();
+        if name == "none" {
+            return Some(EstimateFilter::Unestimated);
+        }
+        let comparison = COMPARISONS
+            .iter()
+            .find(|comparison| name.starts_with(comparison.symbol()))
+            .copied();
+        let estimate = &name[comparison.map_or(0, |comparison| comparison.symbol().len())..];
+        let estimate = Estimate::from_name(estimate)?;
+        Some(EstimateFilter::Compare(
+            comparison.unwrap_or(Comparison::Equal),
+            estimate,
+        ))
+    }
+
+    /// Whether a task passes the filter.
+    pub fn matches(&self, todo: &ToDo) -> bool {
+        let (total, _) = todo.effort();
+        let (comparison, ordering) = match self {
+            EstimateFilter::Unestimated => return total.is_zero(),
+            EstimateFilter::Compare(_, Estimate::Time(_)) if total.time.is_zero() => return false,
+            EstimateFilter::Compare(_, Estimate::Points(_)) if total.points == 0 => return false,
+            EstimateFilter::Compare(comparison, Estimate::Time(time)) => {
+                (*comparison, total.time.cmp(time))
+            }
+            EstimateFilter::Compare(comparison, Estimate::Points(points)) => {
+                (*comparison, total.points.cmp(points))
+            }
+        };
+        comparison.holds(ordering)
+    }
+}
+
+impl fmt::Display for EstimateFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EstimateFilter::Unestimated => write!(f, "none"),
+            EstimateFilter::Compare(comparison, estimate) => {
+                write!(f, "{}{}", comparison, estimate)
+            }
+        }
+    }
+}
+
+/// Generate a random task ID.
+pub fn new_id() -> String {
+    let mut random = RandomState::new().build_hasher().finish();
+    (0..ID_LENGTH)
+        .map(|_| {
+            let ch = ID_CHARS[(random % ID_CHARS.len() as u64) as usize];
+            random /= ID_CHARS.len() as u64;
+            ch as char
+        })
+        .collect()
 }
 
 /// Node in the todo list tree structure.
@@ -20,7 +470,33 @@ pub enum Priority {
 pub struct ToDo {
     pub task: String,
     pub complete: bool,
-    pub priority: Option<Priority>,
+    /// Label of the task's priority level, e.g. 'A'.
+    pub priority: Option<char>,
+    /// Whether sub-tasks are hidden in the outline view.
+    pub folded: bool,
+    /// When the task was created, if known.
+    pub created: Option<NaiveDateTime>,
+    /// When the task was completed, if known.
+    pub completed: Option<NaiveDateTime>,
+    /// Order in which sub-tasks are kept.
+    pub keep_sorted: Option<Sort>,
+    /// How the task recurs once completed.
+    pub recurrence: Option<Recurrence>,
+    /// When the task is next due.
+    pub due: Option<NaiveDate>,
+    /// Time tracked against the task, not counting a running timer.
+    pub spent: Duration,
+    /// Start and length of each period of tracked time.
+    pub sessions: Vec<(NaiveDateTime, Duration)>,
+    /// When the running timer was started, if there is one.
+    pub started: Option<NaiveDateTime>,
+    /// Estimated effort of the task itself, not counting its sub-tasks.
+    pub estimate: Option<Estimate>,
+    /// Short ID that the task can be referred to by, which stays the same
+    /// when it moves.
+    pub id: Option<String>,
+    /// IDs of the tasks that must be completed before this one.
+    pub depends: Vec<String>,
     pub parent: Weak<RefCell<ToDo>>,
     pub sub_tasks: Vec<Rc<RefCell<ToDo>>>,
 }
@@ -33,11 +509,235 @@ impl ToDo {
             task: String::from(task),
             complete: false,
             priority: None,
+            folded: false,
+            created: Some(Local::now().naive_local()),
+            completed: None,
+            keep_sorted: None,
+            recurrence: None,
+            due: None,
+            spent: Duration::zero(),
+            sessions: Vec::new(),
+            started: None,
+            estimate: None,
+            id: Some(new_id()),
+            depends: Vec::new(),
             parent,
             sub_tasks,
         }
     }
 
+    /// Mark the task as completed or not, recording when it was completed.
+    /// Completing a task stops its timer.
+    pub fn set_complete(&mut self, complete: bool) {
+        if complete {
+            self.stop_timer();
+        }
+        if complete && !self.complete {
+            self.completed = Some(Local::now().naive_local());
+        } else if !complete {
+            self.completed = None;
+        }
+        self.complete = complete;
+    }
+
+    /// Create the next occurrence of a completed recurring task, as a copy
+    /// of it and its sub-tasks. The rule moves to the new task, so the
+    /// completed one won't recur again. The copy keeps the same IDs, which
+    /// need renewing once it is in the tree.
+    pub fn next_occurrence(todo: &Rc<RefCell<ToDo>>, reset: bool) -> Option<Rc<RefCell<ToDo>>> {
+        let mut old = todo.borrow_mut();
+        if !old.complete {
+            return None;
+        }
+        let recurrence = old.recurrence.take()?;
+        let completed = old.completed.unwrap_or_else(|| Local::now().naive_local());
+        let due = recurrence.next(old.due, completed.date());
+
+        let next = old.deep_clone(Weak::clone(&old.parent));
+        {
+            let mut todo = next.borrow_mut();
+            todo.set_complete(false);
+            todo.created = Some(Local::now().naive_local());
+            todo.recurrence = Some(recurrence);
+            todo.due = Some(due);
+        }
+        // Time tracked so far belongs to the completed occurrence
+        fn clear_time(todo: &Rc<RefCell<ToDo>>) {
+            let mut todo = todo.borrow_mut();
+            todo.spent = Duration::zero();
+            todo.sessions.clear();
+            todo.started = None;
+            todo.sub_tasks.iter().for_each(clear_time);
+        }
+        clear_time(&next);
+        if reset {
+            fn uncomplete(todo: &Rc<RefCell<ToDo>>) {
+                for sub_task in todo.borrow().sub_tasks.iter() {
+                    sub_task.borrow_mut().set_complete(false);
+                    uncomplete(sub_task);
+                }
+            }
+            uncomplete(&next);
+        }
+        Some(next)
+    }
+
+    /// Start tracking time against the task.
+    pub fn start_timer(&mut self) {
+        if self.started.is_none() {
+            self.started = Some(Local::now().naive_local());
+        }
+    }
+
+    /// Stop the running timer, if any, adding the time to the task.
+    pub fn stop_timer(&mut self) {
+        if let Some(started) = self.started.take() {
+            let duration = Local::now().naive_local() - started;
+            if duration > Duration::zero() {
+                self.spent += duration;
+                self.sessions.push((started, duration));
+            }
+        }
+    }
+
+    /// Time tracked against this task and all tasks below it, including
+    /// running timers. Only time after `since` is counted, if given.
+    pub fn time_spent(&self, since: Option<NaiveDateTime>) -> Duration {
+        let now = Local::now().naive_local();
+        let mut total = match since {
+            Some(since) => self
+                .sessions
+                .iter()
+                .map(|&(start, duration)| {
+                    (start + duration - start.max(since)).max(Duration::zero())
+                })
+                .fold(Duration::zero(), |a, b| a + b),
+            None => self.spent,
+        };
+        if let Some(started) = self.started {
+            let start = since.map_or(started, |since| started.max(since));
+            total += (now - start).max(Duration::zero());
+        }
+        for sub_task in self.sub_tasks.iter() {
+            total += sub_task.borrow().time_spent(since);
+        }
+        total
+    }
+
+    /// Estimated effort of this task and all tasks below it, returning the
+    /// total and the effort remaining on tasks that aren't complete.
+    pub fn effort(&self) -> (Effort, Effort) {
+        let mut total = Effort::zero();
+        if let Some(estimate) = self.estimate {
+            total.add(estimate);
+        }
+        let mut remaining = total;
+        for sub_task in self.sub_tasks.iter() {
+            let (sub_total, sub_remaining) = sub_task.borrow().effort();
+            total += sub_total;
+            remaining += sub_remaining;
+        }
+        if self.complete {
+            remaining = Effort::zero();
+        }
+        (total, remaining)
+    }
+
+    /// Find the task with the given ID, searching this task and all tasks
+    /// below it.
+    pub fn find(todo: &Rc<RefCell<ToDo>>, id: &str) -> Option<Rc<RefCell<ToDo>>> {
+        if todo.borrow().id.as_deref() == Some(id) {
+            return Some(Rc::clone(todo));
+        }
+        todo.borrow()
+            .sub_tasks
+            .iter()
+            .find_map(|sub_task| ToDo::find(sub_task, id))
+    }
+
+    /// Give new IDs to a task and the tasks below it whose IDs are missing
+    /// or already used elsewhere in the tree, e.g. after pasting a copy of a
+    /// task. The root of the tree doesn't need an ID.
+    pub fn renew_duplicate_ids(todo: &Rc<RefCell<ToDo>>, root: &Rc<RefCell<ToDo>>) {
+        fn collect(todo: &Rc<RefCell<ToDo>>, skip: &Rc<RefCell<ToDo>>, ids: &mut HashSet<String>) {
+            if Rc::ptr_eq(todo, skip) {
+                return;
+            }
+            ids.extend(todo.borrow().id.clone());
+            for sub_task in todo.borrow().sub_tasks.iter() {
+                collect(sub_task, skip, ids);
+            }
+        }
+        fn renew(todo: &Rc<RefCell<ToDo>>, root: &Rc<RefCell<ToDo>>, ids: &mut HashSet<String>) {
+            let id = todo.borrow().id.clone();
+            let is_root = Rc::ptr_eq(todo, root);
+            match id {
+                Some(id) if !ids.contains(&id) => {
+                    ids.insert(id);
+                }
+                _ if is_root => (),
+                _ => {
+                    let id = loop {
+                        let id = new_id();
+                        if !ids.contains(&id) && ToDo::find(root, &id).is_none() {
+                            break id;
+                        }
+                    };
+                    ids.insert(id.clone());
+                    todo.borrow_mut().id = Some(id);
+                }
+            }
+            for sub_task in todo.borrow().sub_tasks.iter() {
+                renew(sub_task, root, ids);
+            }
+        }
+        let mut ids = HashSet::new();
+        collect(root, todo, &mut ids);
+        renew(todo, root, &mut ids);
+    }
+
+    /// Whether a task must wait for another, directly or through the tasks
+    /// it depends on.
+    pub fn waits_for(todo: &ToDo, other: &Rc<RefCell<ToDo>>, root: &Rc<RefCell<ToDo>>) -> bool {
+        fn search(
+            todo: &ToDo,
+            other: &Rc<RefCell<ToDo>>,
+            root: &Rc<RefCell<ToDo>>,
+            seen: &mut HashSet<String>,
+        ) -> bool {
+            for id in todo.depends.iter() {
+                if !seen.insert(id.clone()) {
+                    continue;
+                }
+                if let Some(dependency) = ToDo::find(root, id) {
+                    if Rc::ptr_eq(&dependency, other)
+                        || search(&dependency.borrow(), other, root, seen)
+                    {
+                        return true;
+                    }
+                }
+            }
+            false
+        }
+        search(todo, other, root, &mut HashSet::new())
+    }
+
+    /// Whether the task depends on a task that isn't complete. Dependencies
+    /// on tasks that no longer exist are ignored.
+    pub fn is_blocked(&self, root: &Rc<RefCell<ToDo>>) -> bool {
+        self.depends
+            .iter()
+            .any(|id| ToDo::find(root, id).is_some_and(|dependency| !dependency.borrow().complete))
+    }
+
+    /// Find the task below this one with a running timer.
+    pub fn running_timer(todo: &Rc<RefCell<ToDo>>) -> Option<Rc<RefCell<ToDo>>> {
+        if todo.borrow().started.is_some() {
+            return Some(Rc::clone(todo));
+        }
+        todo.borrow().sub_tasks.iter().find_map(ToDo::running_timer)
+    }
+
     /// Find the task hierachy.
     pub fn task_path(&self, path: &mut String) {
         if let Some(parent_todo) = self.parent.upgrade() {
@@ -47,6 +747,57 @@ impl ToDo {
         }
     }
 
+    /// Count all tasks below this one, returning the total and the number
+    /// completed.
+    pub fn count(&self) -> (usize, usize) {
+        let mut total = 0;
+        let mut complete = 0;
+        for sub_task_rc in self.sub_tasks.iter() {
+            let sub_task = sub_task_rc.borrow();
+            let (sub_total, sub_complete) = sub_task.count();
+            total += 1 + sub_total;
+            complete += sub_complete;
+            if sub_task.complete {
+                complete += 1;
+            }
+        }
+        (total, complete)
+    }
+
+    /// Create a copy of this task and all of its sub-tasks, attached to a
+    /// new parent.
+    pub fn deep_clone(&self, parent: Weak<RefCell<ToDo>>) -> Rc<RefCell<ToDo>> {
+        let todo = Rc::new(RefCell::new(ToDo {
+            parent,
+            sub_tasks: Vec::new(),
+            ..self.clone()
+        }));
+        let sub_tasks = self
+            .sub_tasks
+            .iter()
+            .map(|sub_task| sub_task.borrow().deep_clone(Rc::downgrade(&todo)))
+            .collect();
+        todo.borrow_mut().sub_tasks = sub_tasks;
+        todo
+    }
+
+    /// Convert this task and its sub-tasks to save file format.
+    pub fn to_save_string(&self) -> String {
+        let mut buffer = self.to_string();
+        self.all_to_string(1, &mut buffer);
+        buffer
+    }
+
+    /// Convert this task and its sub-tasks to a Markdown checklist.
+    pub fn to_markdown(&self, depth: usize, buf: &mut String) {
+        let check = if self.complete { 'x' } else { ' ' };
+        let pad = "  ".repeat(depth);
+        buf.push_str(&format!("{}- [{}] {}\n", pad, check, self.task));
+        for sub_task in self.sub_tasks.iter() {
+            sub_task.borrow().to_markdown(depth + 1, buf);
+        }
+    }
+
     /// Convert all sub-tasks to string format.
     fn all_to_string(&self, tabs: usize, buf: &mut String) {
         for sub_task_rc in self.sub_tasks.iter() {
@@ -59,31 +810,41 @@ impl ToDo {
         }
     }
 
-    /// Save todo list tree in string format to text file.
-    fn save_current(&self, filename: &Path) {
+    /// Convert the whole tree below this task, as the root, to the contents
+    /// of a save file.
+    pub fn to_file_string(&self) -> String {
         let mut buffer = String::new();
+        if let Some(sort) = self.keep_sorted {
+            buffer.push_str(&format!("sort:{}\n", sort));
+        }
         self.all_to_string(0, &mut buffer);
+        buffer
+    }
 
-        let mut file = match File::create(filename) {
-            Ok(f) => f,
-            Err(err) => {
-                warn!("Unable to open file to save: {}.", err);
-                return;
+    /// Save todo list tree in string format to text file. It is written in
+    /// full next to the file and then renamed over it, so that other
+    /// programs never see it half-written.
+    fn save_current(&self, filename: &Path) -> io::Result<()> {
+        let buffer = self.to_file_string();
+        let name = filename.file_name().unwrap_or_default().to_string_lossy();
+        let temp = filename.with_file_name(format!(".{}.tmp", name));
+        let written = File::create(&temp).and_then(|mut file| {
+            file.write_all(buffer.as_bytes())?;
+            if let Ok(metadata) = fs::metadata(filename) {
+                file.set_permissions(metadata.permissions())?;
             }
-        };
-
-        match file.write_all(buffer.as_bytes()) {
-            Ok(_) => (),
-            Err(err) => {
-                warn!("Unable to write to save file: {}", err);
-                return;
-            }
-        };
+            file.sync_all()
+        });
+        if let Err(err) = written.and_then(|_| fs::rename(&temp, filename)) {
+            let _ = fs::remove_file(&temp);
+            return Err(err);
+        }
         info!("Todo list saved to file.");
+        Ok(())
     }
 
     /// Traverse tree back to root node and save.
-    pub fn save(&self, filename: &Path) {
+    pub fn save(&self, filename: &Path) -> io::Result<()> {
         if let Some(parent_todo) = self.parent.upgrade() {
             parent_todo.borrow().save(filename)
         } else {
@@ -93,33 +854,267 @@ impl ToDo {
 
     /// Convert from string format into ToDo node.
     pub fn from_string(text: &str, parent: Weak<RefCell<ToDo>>) -> ToDo {
-        let complete = match text.chars().nth(1) {
-            Some(ch) => ch == 'X',
+        // Read "[X] (P) " by characters, as the priority label may take more
+        // than one byte
+        let mut chars = text.char_indices();
+        let complete = match chars.nth(1) {
+            Some((_, ch)) => ch == 'X',
             None => false,
         };
 
-        let priority = match text.chars().nth(5) {
-            Some(ch) => match ch {
-                'A' => Some(Priority::High),
-                'B' => Some(Priority::Medium),
-                'C' => Some(Priority::Low),
-                _ => None,
-            },
-            None => None,
+        let priority = match chars.nth(3) {
+            Some((_, ' ')) | None => None,
+            Some((_, ch)) => Some(ch),
         };
 
-        let mut todo = Self::new(&text[8..], parent);
+        let start = chars.nth(2).map_or(text.len(), |(index, _)| index);
+        let (task, metadata) = split_metadata(&text[start..]);
+        let mut todo = Self::new(&unescape_task(task), parent);
         todo.complete = complete;
         todo.priority = priority;
+        todo.created = None;
+        todo.apply_metadata(metadata);
         todo
     }
 
-    /// Reorder subtasks based on priority
-    pub fn sort_by_priority(&mut self) {
-        self.sub_tasks.sort_by(|a, b| {
-            // Reverse order so we treat None properly
-            b.borrow().priority.cmp(&a.borrow().priority)
-        });
+    /// Read the settings of the root of the tree, which are saved on a line
+    /// of their own at the start of the save file.
+    pub fn root_from_string(&mut self, text: &str) {
+        let text = format!(" {}", text);
+        let (_, metadata) = split_metadata(&text);
+        self.apply_metadata(metadata);
+    }
+
+    /// Parse a whole save file into a tree, returning its root.
+    pub fn tree_from_string(text: &str) -> Result<Rc<RefCell<ToDo>>, &'static str> {
+        let root = Rc::new(RefCell::new(ToDo::new("", Weak::new())));
+        let mut lines = text
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .peekable();
+        // Settings for the whole list come first, on a line of metadata alone
+        let is_settings = |line: &&str| {
+            line.split_whitespace()
+                .all(|word| match word.split_once(':') {
+                    Some((key, value)) => METADATA_KEYS.contains(&key) && !value.is_empty(),
+                    None => false,
+                })
+        };
+        if let Some(line) = lines.next_if(is_settings) {
+            root.borrow_mut().root_from_string(line);
+        }
+        // Last task seen at each level of indentation
+        let mut stack = vec![Rc::clone(&root)];
+        for line in lines {
+            let tabs = tab_num(line);
+            if tabs >= stack.len() {
+                return Err("Too much indentation.");
+            }
+            stack.truncate(tabs + 1);
+            let parent = &stack[tabs];
+            let todo = ToDo::from_string(line.trim_start(), Rc::downgrade(parent));
+            let todo = Rc::new(RefCell::new(todo));
+            parent.borrow_mut().sub_tasks.push(Rc::clone(&todo));
+            stack.push(todo);
+        }
+        Ok(root)
+    }
+
+    /// Read the whole todo list tree from a save file.
+    pub(crate) fn read_tree(filename: &Path) -> Result<Rc<RefCell<ToDo>>, ()> {
+        let text = fs::read_to_string(filename)
+            .map_err(|err| warn!("Unable to read {}: {}", filename.display(), err))?;
+        ToDo::tree_from_string(&text).map_err(|err| {
+            warn!("Unable to parse {}: {}", filename.display(), err);
+        })
+    }
+
+    /// Set fields from `key:value` metadata.
+    fn apply_metadata(&mut self, metadata: Vec<(&str, &str)>) {
+        for (key, value) in metadata {
+            match key {
+                "folded" => self.folded = value == "1",
+                "created" => self.created = NaiveDateTime::parse_from_str(value, DATE_FORMAT).ok(),
+                "completed" => {
+                    self.completed = NaiveDateTime::parse_from_str(value, DATE_FORMAT).ok()
+                }
+                "sort" => self.keep_sorted = Sort::from_name(value),
+                "repeat" => self.recurrence = Recurrence::from_name(value),
+                "due" => self.due = NaiveDate::parse_from_str(value, DAY_FORMAT).ok(),
+                "spent" => self.spent = parse_duration(value).unwrap_or_else(Duration::zero),
+                "session" => {
+                    let session = value.split_once('+').and_then(|(start, duration)| {
+                        let start = NaiveDateTime::parse_from_str(start, DATE_FORMAT).ok()?;
+                        Some((start, parse_duration(duration)?))
+                    });
+                    match session {
+                        Some(session) => self.sessions.push(session),
+                        None => warn!("Unable to parse session: {}", value),
+                    }
+                }
+                "started" => self.started = NaiveDateTime::parse_from_str(value, DATE_FORMAT).ok(),
+                "estimate" => self.estimate = Estimate::from_name(value),
+                "id" => self.id = Some(value.to_string()),
+                "depends" => self.depends = value.split(',').map(String::from).collect(),
+                _ => (),
+            }
+        }
+    }
+
+    /// Find the position of a task amongst its siblings.
+    pub fn index(todo: &Rc<RefCell<ToDo>>) -> Option<usize> {
+        let parent = todo.borrow().parent.upgrade()?;
+        let index = parent
+            .borrow()
+            .sub_tasks
+            .iter()
+            .position(|sibling| Rc::ptr_eq(sibling, todo));
+        index
+    }
+
+    /// Make a task the last sub-task of its previous sibling. Returns false
+    /// if there is no previous sibling.
+    pub fn indent(todo: &Rc<RefCell<ToDo>>) -> bool {
+        let parent = match todo.borrow().parent.upgrade() {
+            Some(parent) => parent,
+            None => return false,
+        };
+        let index = match Self::index(todo) {
+            Some(index) if index > 0 => index,
+            _ => return false,
+        };
+
+        let sibling = Rc::clone(&parent.borrow().sub_tasks[index - 1]);
+        parent.borrow_mut().sub_tasks.remove(index);
+        todo.borrow_mut().parent = Rc::downgrade(&sibling);
+        let mut new_parent = sibling.borrow_mut();
+        new_parent.sub_tasks.push(Rc::clone(todo));
+        new_parent.folded = false;
+        true
+    }
+
+    /// Make a task the next sibling of its parent. Returns false if the
+    /// parent is the root of the tree.
+    pub fn outdent(todo: &Rc<RefCell<ToDo>>) -> bool {
+        let parent = match todo.borrow().parent.upgrade() {
+            Some(parent) => parent,
+            None => return false,
+        };
+        let grandparent = match parent.borrow().parent.upgrade() {
+            Some(grandparent) => grandparent,
+            None => return false,
+        };
+        let (index, parent_index) = match (Self::index(todo), Self::index(&parent)) {
+            (Some(index), Some(parent_index)) => (index, parent_index),
+            _ => return false,
+        };
+
+        parent.borrow_mut().sub_tasks.remove(index);
+        todo.borrow_mut().parent = Rc::downgrade(&grandparent);
+        grandparent
+            .borrow_mut()
+            .sub_tasks
+            .insert(parent_index + 1, Rc::clone(todo));
+        true
+    }
+
+    /// Bring the completion of a task and its ancestors in line with their
+    /// sub-tasks: completing tasks whose sub-tasks are all complete, and
+    /// reopening tasks with a sub-task that isn't.
+    pub fn roll_up(todo: &Rc<RefCell<ToDo>>, complete: bool, reopen: bool) {
+        let mut next = Some(Rc::clone(todo));
+        while let Some(todo) = next {
+            next = todo.borrow().parent.upgrade();
+            // The root of the tree isn't a task
+            if next.is_none() {
+                break;
+            }
+            let mut todo = todo.borrow_mut();
+            if todo.sub_tasks.is_empty() {
+                continue;
+            }
+            let all_complete = todo.sub_tasks.iter().all(|t| t.borrow().complete);
+            if all_complete && complete {
+                todo.set_complete(true);
+            } else if !all_complete && reopen {
+                todo.set_complete(false);
+            }
+        }
+    }
+
+    /// Reorder sub-tasks, optionally sorting their own sub-tasks too.
+    pub fn sort(&mut self, sort: Sort, recursive: bool, priorities: &Priorities) {
+        self.sub_tasks
+            .sort_by(|a, b| sort.compare(&a.borrow(), &b.borrow(), priorities));
+        if recursive {
+            for sub_task in self.sub_tasks.iter() {
+                sub_task.borrow_mut().sort(sort, true, priorities);
+            }
+        }
+    }
+
+    /// Keep sub-tasks sorted in the given order, optionally applying the
+    /// same to all tasks below.
+    pub fn set_keep_sorted(&mut self, sort: Option<Sort>, recursive: bool) {
+        self.keep_sorted = sort;
+        if recursive {
+            for sub_task in self.sub_tasks.iter() {
+                sub_task.borrow_mut().set_keep_sorted(sort, true);
+            }
+        }
+    }
+}
+
+/// Saved state of a task and everything below it, used to undo changes.
+pub struct Snapshot {
+    /// The task followed by all tasks below it, with their contents.
+    tasks: Vec<(Rc<RefCell<ToDo>>, ToDo)>,
+    /// Completion of the task's ancestors, which may change with their
+    /// sub-tasks.
+    ancestors: Vec<(Rc<RefCell<ToDo>>, bool, Option<NaiveDateTime>)>,
+}
+
+impl Snapshot {
+    /// Record a task and all tasks below it.
+    pub fn new(parent: &Rc<RefCell<ToDo>>) -> Snapshot {
+        fn record(todo: &Rc<RefCell<ToDo>>, tasks: &mut Vec<(Rc<RefCell<ToDo>>, ToDo)>) {
+            tasks.push((Rc::clone(todo), todo.borrow().clone()));
+            for sub_task in todo.borrow().sub_tasks.iter() {
+                record(sub_task, tasks);
+            }
+        }
+        let mut tasks = Vec::new();
+        record(parent, &mut tasks);
+
+        let mut ancestors = Vec::new();
+        let mut next = parent.borrow().parent.upgrade();
+        while let Some(todo) = next {
+            next = todo.borrow().parent.upgrade();
+            let (complete, completed) = (todo.borrow().complete, todo.borrow().completed);
+            ancestors.push((todo, complete, completed));
+        }
+        Snapshot { tasks, ancestors }
+    }
+
+    /// Task that was recorded along with everything below it.
+    pub fn parent(&self) -> &Rc<RefCell<ToDo>> {
+        &self.tasks[0].0
+    }
+
+    /// Put the recorded tasks back as they were. Tracked time isn't undone.
+    pub fn restore(self) {
+        for (todo, mut state) in self.tasks {
+            let mut todo = todo.borrow_mut();
+            state.spent = todo.spent;
+            state.sessions = std::mem::take(&mut todo.sessions);
+            state.started = todo.started;
+            *todo = state;
+        }
+        for (todo, complete, completed) in self.ancestors {
+            let mut todo = todo.borrow_mut();
+            todo.complete = complete;
+            todo.completed = completed;
+        }
     }
 }
 
@@ -131,12 +1126,273 @@ impl fmt::Display for ToDo {
         }
 
         match self.priority {
-            Some(Priority::Low) => write!(f, "(C) ")?,
-            Some(Priority::Medium) => write!(f, "(B) ")?,
-            Some(Priority::High) => write!(f, "(A) ")?,
+            Some(label) => write!(f, "({}) ", label)?,
             None => write!(f, "( ) ")?,
         }
 
-        writeln!(f, "{}", &self.task)
+        write!(f, "{}", escape_task(&self.task))?;
+        if self.folded {
+            write!(f, " folded:1")?;
+        }
+        if let Some(created) = self.created {
+            write!(f, " created:{}", created.format(DATE_FORMAT))?;
+        }
+        if let Some(completed) = self.completed.filter(|_| self.complete) {
+            write!(f, " completed:{}", completed.format(DATE_FORMAT))?;
+        }
+        if let Some(sort) = self.keep_sorted {
+            write!(f, " sort:{}", sort)?;
+        }
+        if let Some(recurrence) = &self.recurrence {
+            write!(f, " repeat:{}", recurrence)?;
+        }
+        if let Some(due) = self.due {
+            write!(f, " due:{}", due.format(DAY_FORMAT))?;
+        }
+        if let Some(estimate) = self.estimate {
+            write!(f, " estimate:{}", estimate)?;
+        }
+        if let Some(id) = &self.id {
+            write!(f, " id:{}", id)?;
+        }
+        if !self.depends.is_empty() {
+            write!(f, " depends:{}", self.depends.join(","))?;
+        }
+        if self.spent > Duration::zero() {
+            write!(f, " spent:{}", format_duration(self.spent))?;
+        }
+        for (start, duration) in self.sessions.iter() {
+            let start = start.format(DATE_FORMAT);
+            write!(f, " session:{}+{}", start, format_duration(*duration))?;
+        }
+        if let Some(started) = self.started {
+            write!(f, " started:{}", started.format(DATE_FORMAT))?;
+        }
+        writeln!(f)
+    }
+}
+
+/// Check if a word of a task looks like `key:value` metadata with a known
+/// key, once any backslashes escaping it are removed.
+fn looks_like_metadata(word: &str) -> bool {
+    match word.trim_start_matches('\\').split_once(':') {
+        Some((key, value)) => METADATA_KEYS.contains(&key) && !value.is_empty(),
+        None => false,
+    }
+}
+
+/// Escape the words of a task that would be read back as metadata, such as
+/// "due:friday", with a backslash.
+fn escape_task(task: &str) -> String {
+    let words: Vec<String> = task
+        .split(' ')
+        .map(|word| match looks_like_metadata(word) {
+            true => format!("\\{}", word),
+            false => word.to_string(),
+        })
+        .collect();
+    words.join(" ")
+}
+
+/// Remove the backslashes added by `escape_task`.
+fn unescape_task(task: &str) -> String {
+    let words: Vec<&str> = task
+        .split(' ')
+        .map(|word| match word.strip_prefix('\\') {
+            Some(escaped) if looks_like_metadata(escaped) => escaped,
+            _ => word,
+        })
+        .collect();
+    words.join(" ")
+}
+
+/// Split trailing `key:value` metadata (with known keys) from a task.
+fn split_metadata(text: &str) -> (&str, Vec<(&str, &str)>) {
+    let mut task = text.trim_end();
+    let mut metadata = Vec::new();
+    while let Some(start) = task.rfind(' ') {
+        let token = &task[start + 1..];
+        match token.split_once(':') {
+            Some((key, value)) if METADATA_KEYS.contains(&key) && !value.is_empty() => {
+                metadata.push((key, value));
+                task = task[..start].trim_end();
+            }
+            _ => break,
+        }
+    }
+    metadata.reverse();
+    (task, metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Save a task and load it back.
+    fn round_trip(todo: &ToDo) -> ToDo {
+        ToDo::from_string(todo.to_string().trim_end(), Weak::new())
+    }
+
+    #[test]
+    fn metadata_in_task_text_round_trips() {
+        for text in [
+            "email Bob re due:friday",
+            "ask about id:abc123 spent:2h",
+            "literal \\due:x and \\\\id:y",
+        ] {
+            let mut todo = ToDo::new(text, Weak::new());
+            todo.due = NaiveDate::from_ymd_opt(2020, 1, 31);
+            let loaded = round_trip(&todo);
+            assert_eq!(loaded.task, todo.task.trim_end());
+            assert_eq!(loaded.due, todo.due);
+            assert_eq!(loaded.id, todo.id);
+            assert_eq!(loaded.spent, Duration::zero());
+            assert_eq!(loaded.to_string(), todo.to_string());
+        }
+    }
+
+    #[test]
+    fn fields_round_trip() {
+        let mut todo = ToDo::new("water plants", Weak::new());
+        todo.complete = true;
+        todo.priority = Some('H');
+        todo.completed = todo.created;
+        todo.recurrence = Recurrence::from_name("weekly");
+        todo.estimate = Estimate::from_name("2h");
+        todo.depends = vec![String::from("abc123")];
+        todo.spent = Duration::minutes(90);
+        let loaded = round_trip(&todo);
+        assert_eq!(loaded.task, todo.task);
+        assert_eq!(loaded.complete, todo.complete);
+        assert_eq!(loaded.priority, todo.priority);
+        assert_eq!(loaded.recurrence, todo.recurrence);
+        assert_eq!(loaded.estimate, todo.estimate);
+        assert_eq!(loaded.depends, todo.depends);
+        assert_eq!(loaded.spent, todo.spent);
+        assert_eq!(loaded.to_string(), todo.to_string());
+    }
+
+    #[test]
+    fn multi_byte_priority_round_trips() {
+        for label in ['é', '★', 'A'] {
+            let mut todo = ToDo::new("task text", Weak::new());
+            todo.priority = Some(label);
+            let loaded = round_trip(&todo);
+            assert_eq!(loaded.priority, Some(label));
+            assert_eq!(loaded.task, "task text");
+            assert_eq!(loaded.to_string(), todo.to_string());
+        }
+    }
+
+    #[test]
+    fn settings_line_is_only_metadata() {
+        let root = ToDo::tree_from_string("sort:priority\n[ ] ( ) top\n").unwrap();
+        assert!(root.borrow().keep_sorted.is_some());
+        assert_eq!(root.borrow().sub_tasks.len(), 1);
+
+        let indented = ToDo::tree_from_string("    [ ] ( ) child\n[ ] ( ) top\n");
+        assert_eq!(indented.err(), Some("Too much indentation."));
+    }
+
+    #[test]
+    fn estimate_filters_parse() {
+        for name in ["<=2h", ">=3pt", "<30m", ">1h", "=5pt", "none"] {
+            let filter = EstimateFilter::from_name(name).unwrap();
+            assert_eq!(filter.to_string(), name);
+        }
+        let filter = EstimateFilter::from_name("1h").unwrap();
+        assert_eq!(filter.to_string(), "=1h");
+        assert!(EstimateFilter::from_name("<=").is_none());
+    }
+
+    fn day(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn recurrence_names_parse() {
+        use Weekday::{Mon, Thu};
+        assert_eq!(Recurrence::from_name("daily"), Some(Recurrence::Daily));
+        assert_eq!(
+            Recurrence::from_name("Weekly mon thu"),
+            Some(Recurrence::Weekly(vec![Mon, Thu]))
+        );
+        assert_eq!(
+            Recurrence::from_name("weekly"),
+            Some(Recurrence::Weekly(vec![]))
+        );
+        assert_eq!(Recurrence::from_name("after-3"), Some(Recurrence::After(3)));
+        for name in [
+            "weekly-mon-someday",
+            "after-0",
+            "after",
+            "after-3-4",
+            "yearly",
+            "",
+        ] {
+            assert_eq!(Recurrence::from_name(name), None, "{}", name);
+        }
+        let weekly = Recurrence::from_name("weekly-mon-thu").unwrap();
+        assert_eq!(weekly.to_string(), "weekly-mon-thu");
+    }
+
+    #[test]
+    fn monthly_rolls_over_to_month_end() {
+        let monthly = Recurrence::Monthly;
+        let jan_31 = day(2024, 1, 31);
+        assert_eq!(monthly.next(Some(jan_31), jan_31), day(2024, 2, 29));
+        // Keeps to the 31st once past the short month
+        assert_eq!(
+            monthly.next(Some(jan_31), day(2024, 3, 5)),
+            day(2024, 3, 31)
+        );
+        let dec_15 = day(2024, 12, 15);
+        assert_eq!(monthly.next(Some(dec_15), dec_15), day(2025, 1, 15));
+    }
+
+    #[test]
+    fn weekly_follows_weekdays() {
+        let weekly = Recurrence::from_name("weekly-mon-thu").unwrap();
+        // 1 January 2024 was a Monday
+        let monday = day(2024, 1, 1);
+        assert_eq!(weekly.next(Some(monday), monday), day(2024, 1, 4));
+        let thursday = day(2024, 1, 4);
+        assert_eq!(weekly.next(Some(thursday), thursday), day(2024, 1, 8));
+        let every_week = Recurrence::Weekly(vec![]);
+        assert_eq!(every_week.next(Some(thursday), thursday), day(2024, 1, 11));
+    }
+
+    #[test]
+    fn after_counts_from_completion() {
+        let after = Recurrence::After(3);
+        assert_eq!(
+            after.next(Some(day(2024, 1, 1)), day(2024, 1, 10)),
+            day(2024, 1, 13)
+        );
+        assert_eq!(after.next(None, day(2024, 2, 28)), day(2024, 3, 2));
+    }
+
+    #[test]
+    fn missed_occurrences_are_skipped() {
+        let overdue = day(2024, 1, 1);
+        assert_eq!(
+            Recurrence::Daily.next(Some(overdue), day(2024, 1, 5)),
+            day(2024, 1, 6)
+        );
+        assert_eq!(
+            Recurrence::Daily.next(None, day(2024, 1, 5)),
+            day(2024, 1, 6)
+        );
+        let weekly = Recurrence::from_name("weekly-mon-thu").unwrap();
+        // Completed on a Wednesday, two weeks late
+        assert_eq!(
+            weekly.next(Some(overdue), day(2024, 1, 17)),
+            day(2024, 1, 18)
+        );
+        // Completed early, the next occurrence is still the one after it was due
+        assert_eq!(
+            Recurrence::Monthly.next(Some(day(2024, 3, 10)), day(2024, 3, 1)),
+            day(2024, 4, 10)
+        );
     }
 }
@@ -1,20 +1,155 @@
 /// Terminal user interface (TUI) functionality, with ncurses-like API,
 /// built on top of the termion crate.
-use crate::config::Config;
+use crate::config::{Config, Split};
 use log::{error, warn};
+use signal_hook::consts::SIGWINCH;
+use signal_hook::iterator::Signals;
+use std::fmt::Write as FmtWrite;
 use std::io::{Stdin, Stdout, Write};
-use termion::event::Key;
-use termion::input::{Keys, TermRead};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+use termion::event::{Event, Key, MouseEvent};
+use termion::input::TermRead;
 use termion::raw::{IntoRawMode, RawTerminal};
 use termion::{clear, color, cursor, style};
-use unicode_width::UnicodeWidthStr;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Terminal size assumed when the real size cannot be determined.
+const FALLBACK_YX: (usize, usize) = (24, 80);
+
+/// Escape sequence enabling mouse reporting.
+const MOUSE_ON: &str = "\x1b[?1000h\x1b[?1002h\x1b[?1015h\x1b[?1006h";
+/// Escape sequence disabling mouse reporting.
+const MOUSE_OFF: &str = "\x1b[?1006l\x1b[?1015l\x1b[?1002l\x1b[?1000l";
+
+/// Input received by the Window.
+pub enum Input {
+    /// A key pressed by the user.
+    Key(Key),
+    /// A mouse button pressed, released or held (with row and column
+    /// one-indexed, as reported by the terminal).
+    Mouse(MouseEvent),
+    /// The terminal was resized (SIGWINCH).
+    Resize,
+}
+
+/// Colour index of the default foreground/background colour.
+const DEFAULT_COLOUR: usize = 8;
+
+/// A single cell of the screen.
+#[derive(Clone, PartialEq)]
+struct Cell {
+    /// Character in the cell, plus any zero-width characters combined with
+    /// it. Empty if the cell is covered by a wide character to its left.
+    text: String,
+    /// Foreground colour index.
+    fg: usize,
+    /// Background colour index.
+    bg: usize,
+}
+
+impl Cell {
+    /// Create an empty cell with default colours.
+    fn blank() -> Cell {
+        Cell {
+            text: String::from(" "),
+            fg: DEFAULT_COLOUR,
+            bg: DEFAULT_COLOUR,
+        }
+    }
+}
+
+/// A rectangular region of the screen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    /// Top row (zero-indexed).
+    pub y: usize,
+    /// Left column (zero-indexed).
+    pub x: usize,
+    /// Number of rows.
+    pub height: usize,
+    /// Number of columns.
+    pub width: usize,
+}
+
+impl Rect {
+    /// The region inside the rectangle's border.
+    pub fn inner(&self) -> Rect {
+        Rect {
+            y: self.y + 1,
+            x: self.x + 1,
+            height: self.height.saturating_sub(2),
+            width: self.width.saturating_sub(2),
+        }
+    }
+
+    /// Check whether row y, column x (zero-indexed) is inside the rectangle.
+    pub fn contains(&self, y: usize, x: usize) -> bool {
+        y >= self.y && y < self.y + self.height && x >= self.x && x < self.x + self.width
+    }
+
+    /// Divide the rectangle into pieces, sized in proportion to weights.
+    pub fn split(&self, split: Split, weights: &[usize]) -> Vec<Rect> {
+        let total = match split {
+            Split::Vertical => self.width,
+            Split::Horizontal => self.height,
+        };
+        let sum: usize = weights.iter().sum::<usize>().max(1);
+
+        let mut pieces = Vec::new();
+        let mut start = 0;
+        for (i, weight) in weights.iter().enumerate() {
+            // The last piece takes up any remainder
+            let size = if i + 1 == weights.len() {
+                total - start
+            } else {
+                total * weight / sum
+            };
+            pieces.push(match split {
+                Split::Vertical => Rect {
+                    x: self.x + start,
+                    width: size,
+                    ..*self
+                },
+                Split::Horizontal => Rect {
+                    y: self.y + start,
+                    height: size,
+                    ..*self
+                },
+            });
+            start += size;
+        }
+        pieces
+    }
+}
 
 /// A wrapper around the terminal for creating a window.
+///
+/// Drawing functions print into an off-screen buffer; refresh then
+/// compares this with the previously displayed frame and only sends the
+/// cells that changed to the terminal.
 pub struct Window<'a> {
-    /// Key input from Stdin.
-    stdin: Keys<Stdin>,
+    /// Input from Stdin and terminal resize signals.
+    input: Receiver<Input>,
     /// Stdout, with terminal in raw-mode (no input line buffering, no echo).
     stdout: RawTerminal<Stdout>,
+    /// Frame being drawn.
+    buffer: Vec<Cell>,
+    /// Frame currently displayed on the terminal.
+    screen: Vec<Cell>,
+    /// Dimensions (rows, columns) of the frames.
+    size: (usize, usize),
+    /// Colour indices (foreground, background) for subsequent printing.
+    pen: (usize, usize),
+    /// Cursor position (row, column) once the frame is displayed.
+    cursor: (usize, usize),
+    /// Whether the cursor should be visible once the frame is displayed.
+    cursor_visible: bool,
+    /// Whether the cursor is currently visible on the terminal.
+    cursor_shown: bool,
+    /// Whether the terminal must be redrawn from scratch.
+    stale: bool,
     /// Yat configuration.
     pub config: Config<'a>,
 }
@@ -23,7 +158,6 @@ impl<'a> Drop for Window<'a> {
     /// Ensure the terminal is reset if the Window is dropped.
     fn drop(&mut self) {
         self.endwin();
-        self.show_cursor();
     }
 }
 
@@ -37,102 +171,259 @@ impl<'a> Window<'a> {
                 return Err(());
             }
         };
-        Ok(Window {
-            stdin: stdin.keys(),
+        let (tx, rx) = channel();
+        Self::spawn_key_reader(stdin, tx.clone());
+        Self::spawn_resize_listener(tx);
+        let mut window = Window {
+            input: rx,
             stdout: raw,
+            buffer: Vec::new(),
+            screen: Vec::new(),
+            size: (0, 0),
+            pen: (DEFAULT_COLOUR, DEFAULT_COLOUR),
+            cursor: (0, 0),
+            cursor_visible: true,
+            cursor_shown: true,
+            stale: true,
             config,
-        })
+        };
+        window.resize();
+        if window.config.mouse {
+            window.mouse(true);
+        }
+        Ok(window)
+    }
+
+    /// Read keys and mouse events from stdin on a separate thread.
+    fn spawn_key_reader(stdin: Stdin, tx: Sender<Input>) {
+        thread::spawn(move || {
+            for event in stdin.events().flatten() {
+                let input = match event {
+                    Event::Key(key) => Input::Key(key),
+                    Event::Mouse(mouse) => Input::Mouse(mouse),
+                    Event::Unsupported(_) => continue,
+                };
+                if tx.send(input).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    /// Listen for terminal resize signals on a separate thread.
+    fn spawn_resize_listener(tx: Sender<Input>) {
+        let mut signals = match Signals::new([SIGWINCH]) {
+            Ok(signals) => signals,
+            Err(err) => {
+                warn!("Unable to listen for terminal resizes: {}", err);
+                return;
+            }
+        };
+        thread::spawn(move || {
+            for _ in signals.forever() {
+                if tx.send(Input::Resize).is_err() {
+                    break;
+                }
+            }
+        });
     }
 
     /// Find the terminal's dimensions.
     pub fn get_max_yx(&self) -> (usize, usize) {
-        let (y, x) = termion::terminal_size().unwrap_or_else(|err| {
-            warn!("Unable to determine terminal size: {}.", err);
-            (0, 0)
-        });
-        (x as usize, y as usize)
+        match termion::terminal_size() {
+            Ok((x, y)) if x > 0 && y > 0 => (y as usize, x as usize),
+            Ok(_) => {
+                warn!("Terminal reported zero size.");
+                FALLBACK_YX
+            }
+            Err(err) => {
+                warn!("Unable to determine terminal size: {}.", err);
+                FALLBACK_YX
+            }
+        }
+    }
+
+    /// Match the size of the frames to the terminal, forcing a full
+    /// redraw if it has changed.
+    fn resize(&mut self) {
+        let size = self.get_max_yx();
+        if size != self.size {
+            self.size = size;
+            self.buffer = vec![Cell::blank(); size.0 * size.1];
+            self.screen = self.buffer.clone();
+            self.stale = true;
+        }
     }
 
     /// Hide cursor from terminal.
     pub fn hide_cursor(&mut self) {
-        write!(self.stdout, "{}", cursor::Hide).unwrap_or_else(|err| {
-            warn!("Unable to hide cursor: {}.", err);
-        });
+        self.cursor_visible = false;
     }
 
     /// Display cursor on terminal.
     pub fn show_cursor(&mut self) {
-        write!(self.stdout, "{}", cursor::Show).unwrap_or_else(|err| {
-            warn!("Unable to show cursor: {}", err);
-        });
+        self.cursor_visible = true;
+    }
+
+    /// Look up the colour with the given index (8 is the default
+    /// foreground or background colour).
+    fn colour(&self, index: usize, background: bool) -> &'a dyn color::Color {
+        match index {
+            0 => self.config.colour0,
+            1 => self.config.colour1,
+            2 => self.config.colour2,
+            3 => self.config.colour3,
+            4 => self.config.colour4,
+            5 => self.config.colour5,
+            6 => self.config.colour6,
+            7 => self.config.colour7,
+            _ if background => self.config.colourbg,
+            _ => self.config.colourfg,
+        }
     }
 
-    /// Flush stdout buffer to terminal.
+    /// Display the drawn frame on the terminal, sending only the cells that
+    /// differ from the previous frame.
     pub fn refresh(&mut self) {
+        let (_, width) = self.size;
+        let mut out = String::new();
+        let mut pos = None; // terminal cursor position
+        let mut pen = None; // terminal colours
+
+        if self.cursor_shown {
+            out.push_str(cursor::Hide.as_ref());
+            self.cursor_shown = false;
+        }
+
+        if self.stale {
+            // Clear with the default colours and only draw non-blank cells
+            let _ = write!(
+                out,
+                "{}{}{}",
+                color::Fg(self.colour(DEFAULT_COLOUR, false)),
+                color::Bg(self.colour(DEFAULT_COLOUR, true)),
+                clear::All
+            );
+            pen = Some((DEFAULT_COLOUR, DEFAULT_COLOUR));
+            for cell in self.screen.iter_mut() {
+                *cell = Cell::blank();
+            }
+            self.stale = false;
+        }
+
+        for (i, cell) in self.buffer.iter().enumerate() {
+            if *cell == self.screen[i] {
+                continue;
+            }
+            self.screen[i].clone_from(cell);
+            if cell.text.is_empty() {
+                // Covered by the wide character to the left
+                continue;
+            }
+
+            let (y, x) = (i / width, i % width);
+            if pos != Some((y, x)) {
+                let _ = write!(out, "{}", cursor::Goto(1 + x as u16, 1 + y as u16));
+            }
+            if pen != Some((cell.fg, cell.bg)) {
+                let _ = write!(
+                    out,
+                    "{}{}",
+                    color::Fg(self.colour(cell.fg, false)),
+                    color::Bg(self.colour(cell.bg, true))
+                );
+                pen = Some((cell.fg, cell.bg));
+            }
+            out.push_str(&cell.text);
+            pos = Some((y, x + UnicodeWidthStr::width(cell.text.as_str()).max(1)));
+        }
+
+        if self.cursor_visible {
+            let (y, x) = self.cursor;
+            let _ = write!(
+                out,
+                "{}{}",
+                cursor::Goto(1 + x as u16, 1 + y as u16),
+                cursor::Show
+            );
+            self.cursor_shown = true;
+        }
+
+        self.stdout.write_all(out.as_bytes()).unwrap_or_else(|err| {
+            warn!("Unable to write to stdout: {}", err);
+        });
         self.stdout.flush().unwrap_or_else(|err| {
             warn!("Unable to flush stdout: {}", err);
         });
     }
 
-    /// Return the key input from stdin.
+    /// Turn mouse reporting on or off.
+    fn mouse(&mut self, on: bool) {
+        let sequence = if on { MOUSE_ON } else { MOUSE_OFF };
+        write!(self.stdout, "{}", sequence).unwrap_or_else(|err| {
+            warn!("Unable to set mouse reporting: {}", err);
+        });
+    }
+
+    /// Copy text to the system clipboard through the terminal, using an
+    /// OSC 52 escape sequence. Terminals without support ignore it.
+    pub fn copy_to_clipboard(&mut self, text: &str) {
+        let sequence = format!("\x1b]52;c;{}\x07", base64::encode(text));
+        write!(self.stdout, "{}", sequence).unwrap_or_else(|err| {
+            warn!("Unable to copy to clipboard: {}", err);
+        });
+        self.stdout.flush().unwrap_or_else(|err| {
+            warn!("Unable to flush stdout: {}", err);
+        });
+    }
+
+    /// Return the next input from stdin, or a notification that the
+    /// terminal was resized.
+    pub fn getinput(&mut self) -> Option<Input> {
+        let input = self.input.recv().ok();
+        if let Some(Input::Resize) = input {
+            self.resize();
+        }
+        input
+    }
+
+    /// Like getinput, but give up waiting after a while, returning None.
+    pub fn getinput_timeout(&mut self, timeout: Duration) -> Option<Input> {
+        let input = self.input.recv_timeout(timeout).ok();
+        if let Some(Input::Resize) = input {
+            self.resize();
+        }
+        input
+    }
+
+    /// Return the key input from stdin, ignoring the mouse. Returns None if
+    /// the terminal was resized, in which case the caller should redraw.
     pub fn getch(&mut self) -> Option<Key> {
-        match self.stdin.next() {
-            Some(Ok(key)) => Some(key),
-            _ => None,
+        loop {
+            match self.getinput() {
+                Some(Input::Key(key)) => return Some(key),
+                Some(Input::Mouse(_)) => (),
+                _ => return None,
+            }
         }
     }
 
     /// Move the cursor to position at row y, column x (zero-indexed).
     pub fn mv(&mut self, y: usize, x: usize) {
-        write!(self.stdout, "{}", cursor::Goto(1 + x as u16, 1 + y as u16)).unwrap_or_else(|err| {
-            warn!("Unable to mv cursor: {}", err);
-        });
+        self.cursor = (y, x);
     }
 
     /// Add colour to subsequent printed text.
     pub fn colour_on(&mut self, fg: usize, bg: usize) {
-        let fgcol = match fg {
-            0 => self.config.colour0,
-            1 => self.config.colour1,
-            2 => self.config.colour2,
-            3 => self.config.colour3,
-            4 => self.config.colour4,
-            5 => self.config.colour5,
-            6 => self.config.colour6,
-            7 => self.config.colour7,
-            8 => self.config.colourfg,
-            _ => return,
-        };
-
-        let bgcol = match bg {
-            0 => self.config.colour0,
-            1 => self.config.colour1,
-            2 => self.config.colour2,
-            3 => self.config.colour3,
-            4 => self.config.colour4,
-            5 => self.config.colour5,
-            6 => self.config.colour6,
-            7 => self.config.colour7,
-            8 => self.config.colourbg,
-            _ => return,
-        };
-
-        write!(self.stdout, "{}{}", color::Fg(fgcol), color::Bg(bgcol)).unwrap_or_else(|err| {
-            warn!("Unable to turn colour on: {}", err);
-        });
+        if fg > DEFAULT_COLOUR || bg > DEFAULT_COLOUR {
+            return;
+        }
+        self.pen = (fg, bg);
     }
 
     /// Reset colours to default foreground and background.
     pub fn colour_off(&mut self) {
-        write!(
-            self.stdout,
-            "{}{}",
-            color::Fg(self.config.colourfg),
-            color::Bg(self.config.colourbg)
-        )
-        .unwrap_or_else(|err| {
-            warn!("Unable to turn colour off: {}", err);
-        });
+        self.pen = (DEFAULT_COLOUR, DEFAULT_COLOUR);
     }
 
     /// Reset colours to terminal defaults.
@@ -148,17 +439,64 @@ impl<'a> Window<'a> {
         });
     }
 
+    /// Place a character of the given displayed width in the cell at row
+    /// y, column x (zero-indexed), tidying up any wide characters it
+    /// overlaps.
+    fn put(&mut self, y: usize, x: usize, ch: char, w: usize) {
+        let (_, width) = self.size;
+        let i = y * width + x;
+
+        // Overwriting the right half of a wide character
+        if self.buffer[i].text.is_empty() && x > 0 {
+            self.buffer[i - 1].text = String::from(" ");
+        }
+        // Overwriting the left half of a wide character
+        if x + w < width && self.buffer[i + w].text.is_empty() {
+            self.buffer[i + w].text = String::from(" ");
+        }
+
+        let (fg, bg) = self.pen;
+        let cell = &mut self.buffer[i];
+        cell.text.clear();
+        cell.text.push(ch);
+        cell.fg = fg;
+        cell.bg = bg;
+        if w == 2 {
+            let cell = &mut self.buffer[i + 1];
+            cell.text.clear();
+            cell.fg = fg;
+            cell.bg = bg;
+        }
+    }
+
     /// Print text at row y, column x (zero-indexed).
     pub fn mvprintw(&mut self, y: usize, x: usize, text: &str) {
-        write!(
-            self.stdout,
-            "{}{}",
-            cursor::Goto(1 + x as u16, 1 + y as u16),
-            text
-        )
-        .unwrap_or_else(|err| {
-            warn!("Unable to mvprintw: {}", err);
-        });
+        let (height, width) = self.size;
+        if y >= height {
+            return;
+        }
+
+        let mut x = x;
+        for ch in text.chars() {
+            match UnicodeWidthChar::width(ch) {
+                Some(0) if x > 0 && x <= width => {
+                    // Combine with the preceding character
+                    let mut i = y * width + x - 1;
+                    if self.buffer[i].text.is_empty() && i > 0 {
+                        i -= 1;
+                    }
+                    self.buffer[i].text.push(ch);
+                }
+                Some(w) => {
+                    if x + w > width {
+                        break;
+                    }
+                    self.put(y, x, ch, w);
+                    x += w;
+                }
+                _ => (),
+            }
+        }
     }
 
     /// Print text at row y, column x (zero-indexed), truncated to ensure
@@ -166,6 +504,9 @@ impl<'a> Window<'a> {
     pub fn wrap_print(&mut self, y: usize, x: usize, width: usize, text: &str) {
         let len = UnicodeWidthStr::width(text); // displayed width
         let mut end = text.len();
+        if width < 3 {
+            return;
+        }
         if len > width - 3 {
             self.mvprintw(y, x + width - 3, "...");
             let mut n = (len - (width - 3)) as isize;
@@ -185,6 +526,9 @@ impl<'a> Window<'a> {
     pub fn border(&mut self, lower_left: (usize, usize), dimensions: (usize, usize)) {
         let (y, x) = lower_left;
         let (height, width) = dimensions;
+        if height < 2 || width < 2 || height > y + 1 {
+            return;
+        }
 
         self.mvprintw(y + 1 - height, x, self.config.ulcorner);
         self.mvprintw(y, x, self.config.llcorner);
@@ -203,39 +547,67 @@ impl<'a> Window<'a> {
         }
     }
 
+    /// Print a panel: a border with a title.
+    pub fn panel(&mut self, rect: &Rect, title: &str) {
+        self.border(
+            (rect.y + rect.height.max(1) - 1, rect.x),
+            (rect.height, rect.width),
+        );
+        if UnicodeWidthStr::width(title) + 4 <= rect.width {
+            self.colour_on(4, 8);
+            self.mvprintw(rect.y, rect.x + 2, title);
+            self.colour_off();
+        }
+    }
+
     /// Fill a rectangular region with character ch.
     pub fn rectangle(&mut self, ch: char, lower_left: (usize, usize), dimensions: (usize, usize)) {
         let (y, x) = lower_left;
         let (height, width) = dimensions;
         let mut buf = [0; 4];
         let c = ch.encode_utf8(&mut buf);
+        if height > y + 1 {
+            return;
+        }
 
-        for j in (y - height + 1)..(y + 1) {
+        for j in (y + 1 - height)..(y + 1) {
             for i in x..(x + width) {
                 self.mvprintw(j, i, c);
             }
         }
     }
 
-    /// Clear stdout.
+    /// Clear the frame being drawn.
     pub fn clear(&mut self) {
-        write!(self.stdout, "{}", clear::All).unwrap_or_else(|err| {
-            warn!("Unable to clear stdout: {}", err);
-        });
+        self.resize();
+        for cell in self.buffer.iter_mut() {
+            cell.text.clear();
+            cell.text.push(' ');
+            cell.fg = DEFAULT_COLOUR;
+            cell.bg = DEFAULT_COLOUR;
+        }
     }
 
     /// Reset stdout.
     pub fn endwin(&mut self) {
+        if self.config.mouse {
+            self.mouse(false);
+        }
         self.colour_reset();
         write!(
             self.stdout,
-            "{}{}{}",
+            "{}{}{}{}",
             clear::All,
             style::Reset,
-            cursor::Goto(1, 1)
+            cursor::Goto(1, 1),
+            cursor::Show
         )
         .unwrap_or_else(|err| {
             warn!("Unable to endwin: {}", err);
         });
+        self.stdout.flush().unwrap_or_else(|err| {
+            warn!("Unable to flush stdout: {}", err);
+        });
+        self.stale = true;
     }
 }